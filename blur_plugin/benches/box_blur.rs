@@ -0,0 +1,46 @@
+//! Сравнение наивного размытия с O(1)-на-пиксель реализацией
+//!
+//! Запуск: `cargo bench -p blur_plugin`
+
+use blur_plugin::box_blur::{box_blur, naive_box_blur};
+use blur_plugin::edge::EdgeMode;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const WIDTH: usize = 512;
+const HEIGHT: usize = 512;
+
+fn time(mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+fn main() {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let src: Vec<u8> = (0..WIDTH * HEIGHT * 4)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 32) as u8
+        })
+        .collect();
+    let mut naive = vec![0; src.len()];
+    let mut fast = vec![0; src.len()];
+
+    println!("{WIDTH}×{HEIGHT}, один проход");
+    let edge = EdgeMode::Shrink;
+    for radius in [1, 4, 16, 32] {
+        let naive_time = time(|| naive_box_blur(&src, &mut naive, WIDTH, HEIGHT, radius, edge));
+        let fast_time = time(|| {
+            box_blur(&src, &mut fast, WIDTH, HEIGHT, radius, edge, 1, &|_| Ok(())).unwrap();
+            black_box(&fast);
+        });
+        assert_eq!(naive, fast, "результаты расходятся при radius = {radius}");
+        println!(
+            "radius {radius:>3}: наивно {naive_time:>10.2?}, быстро {fast_time:>10.2?}, ускорение ×{:.1}",
+            naive_time.as_secs_f64() / fast_time.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! Премультиплицированная альфа
+//!
+//! Размытие независимых каналов RGBA тянет цвет полностью прозрачных
+//! пикселей в соседние, давая тёмные или цветные ореолы. Перед размытием
+//! цвет умножается на альфу, а после — делится обратно. Чтобы не терять
+//! точность, премультиплицированные значения хранятся в `u16` точно:
+//! цвет как `c * a`, альфа как `a * 255`, то есть в одном масштабе.
+
+use plugin_sdk::CHANNELS;
+
+/// RGBA8 → премультиплицированные `[r * a, g * a, b * a, a * 255]`.
+pub fn premultiply(src: &[u8]) -> Vec<u16> {
+    src.chunks_exact(CHANNELS)
+        .flat_map(|pixel| {
+            let alpha = u16::from(pixel[3]);
+            [
+                u16::from(pixel[0]) * alpha,
+                u16::from(pixel[1]) * alpha,
+                u16::from(pixel[2]) * alpha,
+                alpha * 255,
+            ]
+        })
+        .collect()
+}
+
+/// Обратное к [`premultiply`] преобразование в `dst`.
+///
+/// При `round = false` результат усекается, как и среднее квадратного
+/// размытия, — для непрозрачных пикселей это даёт тот же результат, что и
+/// размытие без премультипликации. Полностью прозрачный пиксель становится
+/// прозрачным чёрным.
+pub fn unpremultiply(src: &[u16], dst: &mut [u8], round: bool) {
+    for (pixel, out) in src
+        .chunks_exact(CHANNELS)
+        .zip(dst.chunks_exact_mut(CHANNELS))
+    {
+        let alpha = u32::from(pixel[3]);
+        if alpha == 0 {
+            out.fill(0);
+            continue;
+        }
+        let bias = |divisor: u32| if round { divisor / 2 } else { 0 };
+        for c in 0..3 {
+            let value = (u32::from(pixel[c]) * 255 + bias(alpha)) / alpha;
+            out[c] = value.min(255) as u8;
+        }
+        out[3] = ((alpha + bias(255)) / 255) as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let src: Vec<u8> = (0..=255).flat_map(|a| [a, 255 - a, 7, a]).collect();
+        let premultiplied = premultiply(&src);
+        let mut dst = vec![0; src.len()];
+        for round in [false, true] {
+            unpremultiply(&premultiplied, &mut dst, round);
+            for (before, after) in src.chunks_exact(4).zip(dst.chunks_exact(4)) {
+                if before[3] == 0 {
+                    assert_eq!(after, [0, 0, 0, 0]);
+                } else {
+                    assert_eq!(after, before);
+                }
+            }
+        }
+    }
+}
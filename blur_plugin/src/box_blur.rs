@@ -0,0 +1,346 @@
+//! Квадратное размытие (box blur) за O(1) на пиксель
+//!
+//! Окно `(2r + 1) × (2r + 1)` за границей изображения достраивается по
+//! [`EdgeMode`], а результат — целая часть среднего по учтённым пикселям.
+//! Сумма окна раскладывается на горизонтальные суммы строк, которые
+//! скользящим окном складываются по вертикали, поэтому время не зависит от
+//! радиуса. Делится только итоговая точная сумма, так что результат
+//! совпадает с наивным алгоритмом бит в бит.
+//!
+//! Строки результата делятся на полосы по потокам (см. [`crate::parallel`]),
+//! и в каждой полосе окно по вертикали собирается заново; суммы целые,
+//! поэтому разбиение на результат не влияет.
+
+use crate::channel::Channel;
+use crate::edge::{EdgeMode, Sample};
+use crate::parallel::{Checkpoint, RowCounter, for_each_band};
+use plugin_sdk::{CHANNELS, Result};
+use std::ops::Range;
+
+/// Скользящее окно радиуса `radius` вдоль оси длины `len` над индексами
+/// `range`.
+///
+/// Начальная сумма окна вокруг `range.start` собирается с кратностями
+/// [`EdgeMode::multiplicity`], а при каждом сдвиге один индекс входит в окно
+/// и один выходит. `add(k, n)` прибавляет `n` раз значение с индексом `k`,
+/// `remove(k)` вычитает его один раз, `emit(i)` вызывается, когда окно
+/// стоит над индексом `i`; ошибка из `emit` прерывает проход.
+#[allow(clippy::too_many_arguments)]
+fn slide<S>(
+    state: &mut S,
+    len: usize,
+    range: Range<usize>,
+    radius: usize,
+    edge: EdgeMode,
+    add: impl Fn(&mut S, usize, u64),
+    remove: impl Fn(&mut S, usize),
+    mut emit: impl FnMut(&mut S, usize) -> Result<()>,
+) -> Result<()> {
+    for k in 0..len {
+        let count = edge.multiplicity(k, range.start, radius, len);
+        if count > 0 {
+            add(state, k, count);
+        }
+    }
+    let start = range.start;
+    let radius = radius as i64;
+    for i in range {
+        if i > start {
+            let entering = edge.sample(i as i64 + radius, len);
+            let leaving = edge.sample(i as i64 - radius - 1, len);
+            // Окно длиннее периода: вошёл и вышел один и тот же пиксель
+            if entering != leaving {
+                if let Sample::Pixel(k) = entering {
+                    add(state, k, 1);
+                }
+                if let Sample::Pixel(k) = leaving {
+                    remove(state, k);
+                }
+            }
+        }
+        emit(state, i)?;
+    }
+    Ok(())
+}
+
+/// Горизонтальные суммы окна для каждого пикселя строки `row`.
+fn row_sums<T: Channel>(row: &[T], radius: usize, edge: EdgeMode, out: &mut [u64]) {
+    let width = row.len() / CHANNELS;
+    let channels = |k: usize| &row[k * CHANNELS..(k + 1) * CHANNELS];
+    let _ = slide(
+        &mut [0u64; CHANNELS],
+        width,
+        0..width,
+        radius,
+        edge,
+        |acc, k, count| {
+            for (sum, &value) in acc.iter_mut().zip(channels(k)) {
+                *sum += count * value.to_u64();
+            }
+        },
+        |acc, k| {
+            for (sum, &value) in acc.iter_mut().zip(channels(k)) {
+                *sum -= value.to_u64();
+            }
+        },
+        |acc, x| {
+            out[x * CHANNELS..(x + 1) * CHANNELS].copy_from_slice(acc);
+            Ok(())
+        },
+    );
+}
+
+/// Один проход размытия `src` в `dst` (оба — RGBA `width × height` с
+/// каналами `T`) в `threads` потоках.
+///
+/// `checkpoint` вызывается перед каждой строкой результата с долей готовых
+/// строк; ошибка из него прерывает проход.
+#[allow(clippy::too_many_arguments)]
+pub fn box_blur<T: Channel>(
+    src: &[T],
+    dst: &mut [T],
+    width: usize,
+    height: usize,
+    radius: usize,
+    edge: EdgeMode,
+    threads: usize,
+    checkpoint: Checkpoint<'_>,
+) -> Result<()> {
+    if width == 0 || height == 0 {
+        return Ok(());
+    }
+    let radius_x = edge.effective_radius(radius, width)?;
+    let radius_y = edge.effective_radius(radius, height)?;
+    let row_len = width * CHANNELS;
+    let counts: Vec<u64> = (0..width)
+        .map(|x| edge.window_len(x, radius_x, width))
+        .collect();
+    let row = |y: usize| &src[y * row_len..(y + 1) * row_len];
+    let progress = RowCounter::new(height, checkpoint);
+
+    for_each_band(dst, row_len, threads, |rows, band| {
+        let first = rows.start;
+        // Суммы окна по вертикали для каждого столбца и канала и суммы одной строки
+        let mut state = (vec![0u64; row_len], vec![0u64; row_len]);
+        slide(
+            &mut state,
+            height,
+            rows,
+            radius_y,
+            edge,
+            |(columns, sums), y, count| {
+                row_sums(row(y), radius_x, edge, sums);
+                for (column, &sum) in columns.iter_mut().zip(sums.iter()) {
+                    *column += count * sum;
+                }
+            },
+            |(columns, sums), y| {
+                row_sums(row(y), radius_x, edge, sums);
+                for (column, &sum) in columns.iter_mut().zip(sums.iter()) {
+                    *column -= sum;
+                }
+            },
+            |(columns, _), y| {
+                progress.tick()?;
+                let window_rows = edge.window_len(y, radius_y, height);
+                let out = &mut band[(y - first) * row_len..(y - first + 1) * row_len];
+                for (x, &count) in counts.iter().enumerate() {
+                    let count = window_rows * count;
+                    for c in 0..CHANNELS {
+                        let i = x * CHANNELS + c;
+                        out[i] = T::from_u64(columns[i] / count);
+                    }
+                }
+                Ok(())
+            },
+        )
+    })
+}
+
+/// Наивный проход за O(radius²) на пиксель: эталон для тестов и бенчмарка.
+pub fn naive_box_blur<T: Channel>(
+    src: &[T],
+    dst: &mut [T],
+    width: usize,
+    height: usize,
+    radius: usize,
+    edge: EdgeMode,
+) {
+    let (radius_x, radius_y) = match edge {
+        EdgeMode::Shrink => (radius.min(width) as i64, radius.min(height) as i64),
+        _ => (radius as i64, radius as i64),
+    };
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0u64; CHANNELS];
+            let mut count = 0u64;
+            for dy in -radius_y..=radius_y {
+                for dx in -radius_x..=radius_x {
+                    let sy = edge.sample(y as i64 + dy, height);
+                    let sx = edge.sample(x as i64 + dx, width);
+                    match (sx, sy) {
+                        (Sample::Pixel(nx), Sample::Pixel(ny)) => {
+                            let idx = (ny * width + nx) * CHANNELS;
+                            for c in 0..CHANNELS {
+                                sum[c] += src[idx + c].to_u64();
+                            }
+                        }
+                        (Sample::Skip, _) | (_, Sample::Skip) => continue,
+                        _ => {}
+                    }
+                    count += 1;
+                }
+            }
+            let idx = (y * width + x) * CHANNELS;
+            for c in 0..CHANNELS {
+                dst[idx + c] = T::from_u64(sum[c] / count);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Детерминированный генератор псевдослучайных байт (xorshift)
+    fn random_image(width: usize, height: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed | 1;
+        (0..width * height * CHANNELS)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 32) as u8
+            })
+            .collect()
+    }
+
+    const EDGE_MODES: [EdgeMode; 5] = [
+        EdgeMode::Shrink,
+        EdgeMode::Clamp,
+        EdgeMode::Mirror,
+        EdgeMode::Wrap,
+        EdgeMode::Transparent,
+    ];
+
+    fn blur_both(
+        src: &[u8],
+        width: usize,
+        height: usize,
+        radius: usize,
+        edge: EdgeMode,
+    ) -> (Vec<u8>, Vec<u8>) {
+        let mut fast = vec![0; src.len()];
+        box_blur(src, &mut fast, width, height, radius, edge, 1, &|_| Ok(())).unwrap();
+        let mut naive = vec![0; src.len()];
+        naive_box_blur(src, &mut naive, width, height, radius, edge);
+        (fast, naive)
+    }
+
+    #[test]
+    fn test_matches_naive_on_random_images() {
+        let sizes = [(1, 1), (1, 7), (7, 1), (5, 3), (17, 11), (32, 32)];
+        for (seed, &(width, height)) in sizes.iter().enumerate() {
+            let src = random_image(width, height, seed as u64 + 1);
+            for radius in [0, 1, 2, 3, 5, 8, 40] {
+                for edge in EDGE_MODES {
+                    let (fast, naive) = blur_both(&src, width, height, radius, edge);
+                    assert_eq!(fast, naive, "{width}×{height}, radius {radius}, {edge:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_huge_radius() {
+        // Радиус больше изображения: каждый пиксель — среднее всего изображения
+        let src = random_image(6, 4, 42);
+        let (fast, naive) = blur_both(&src, 6, 4, usize::MAX, EdgeMode::Shrink);
+        assert_eq!(fast, naive);
+        assert!(fast.chunks_exact(CHANNELS).all(|p| p == &fast[..CHANNELS]));
+    }
+
+    #[test]
+    fn test_zero_radius_is_identity() {
+        let src = random_image(9, 5, 7);
+        for edge in EDGE_MODES {
+            let (fast, _) = blur_both(&src, 9, 5, 0, edge);
+            assert_eq!(fast, src, "{edge:?}");
+        }
+    }
+
+    #[test]
+    fn test_edge_modes_pinned() {
+        // Строка 3×1 с красным 0, 30, 90 и непрозрачной альфой, радиус 1
+        let src: [u8; 12] = [0, 0, 0, 255, 30, 0, 0, 255, 90, 0, 0, 255];
+        let expected = [
+            (EdgeMode::Shrink, [15, 40, 60], [255, 255, 255]),
+            (EdgeMode::Clamp, [10, 40, 70], [255, 255, 255]),
+            (EdgeMode::Mirror, [20, 40, 50], [255, 255, 255]),
+            (EdgeMode::Wrap, [40, 40, 40], [255, 255, 255]),
+            // Сверху и снизу — прозрачные строки, окно всегда 3×3
+            (EdgeMode::Transparent, [3, 13, 13], [56, 85, 56]),
+        ];
+        for (edge, red, alpha) in expected {
+            let mut dst = [0; 12];
+            box_blur(&src, &mut dst, 3, 1, 1, edge, 1, &|_| Ok(())).unwrap();
+            let actual: Vec<_> = dst.chunks_exact(CHANNELS).map(|p| (p[0], p[3])).collect();
+            assert_eq!(
+                actual,
+                red.into_iter().zip(alpha).collect::<Vec<_>>(),
+                "{edge:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_wrap_radius_larger_than_image() {
+        // Окно в несколько периодов: почти среднее по всему изображению
+        let src = random_image(5, 4, 3);
+        let (fast, naive) = blur_both(&src, 5, 4, 23, EdgeMode::Wrap);
+        assert_eq!(fast, naive);
+    }
+
+    #[test]
+    fn test_radius_limit() {
+        let src = random_image(2, 2, 5);
+        let mut dst = vec![0; src.len()];
+        let edge = EdgeMode::Clamp;
+        let error = box_blur(&src, &mut dst, 2, 2, usize::MAX, edge, 1, &|_| Ok(())).unwrap_err();
+        assert_eq!(error.code(), plugin_sdk::ERROR_INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_threads_match_single_thread() {
+        let (width, height) = (13, 17);
+        let src = random_image(width, height, 9);
+        for edge in EDGE_MODES {
+            for radius in [0, 2, 6, 30] {
+                let mut single = vec![0; src.len()];
+                box_blur(&src, &mut single, width, height, radius, edge, 1, &|_| {
+                    Ok(())
+                })
+                .unwrap();
+                for threads in [2, 4, 7, 64] {
+                    let mut multi = vec![0; src.len()];
+                    box_blur(
+                        &src,
+                        &mut multi,
+                        width,
+                        height,
+                        radius,
+                        edge,
+                        threads,
+                        &|_| Ok(()),
+                    )
+                    .unwrap();
+                    assert_eq!(
+                        multi, single,
+                        "{edge:?}, radius {radius}, {threads} потоков"
+                    );
+                }
+            }
+        }
+    }
+}
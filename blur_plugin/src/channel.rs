@@ -0,0 +1,48 @@
+//! Типы каналов, с которыми работают ядра размытия
+//!
+//! Изображение приходит в RGBA8, но премультиплицированные данные хранятся
+//! в `u16`, чтобы умножение на альфу не теряло точность.
+
+/// Целочисленный канал пикселя.
+pub trait Channel: Copy + Default + Send + Sync + 'static {
+    /// Наибольшее значение канала.
+    const MAX: u64;
+
+    /// Значение канала для целочисленных сумм.
+    fn to_u64(self) -> u64;
+
+    /// Обратное преобразование; `value` не больше [`Channel::MAX`].
+    fn from_u64(value: u64) -> Self;
+
+    /// Значение канала для вещественных свёрток.
+    fn to_f32(self) -> f32;
+
+    /// Округляет вещественный результат свёртки до ближайшего значения канала.
+    fn from_f32(value: f32) -> Self;
+}
+
+macro_rules! impl_channel {
+    ($($ty:ty),*) => {$(
+        impl Channel for $ty {
+            const MAX: u64 = <$ty>::MAX as u64;
+
+            fn to_u64(self) -> u64 {
+                u64::from(self)
+            }
+
+            fn from_u64(value: u64) -> Self {
+                value as $ty
+            }
+
+            fn to_f32(self) -> f32 {
+                f32::from(self)
+            }
+
+            fn from_f32(value: f32) -> Self {
+                value.round().clamp(0.0, <$ty>::MAX as f32) as $ty
+            }
+        }
+    )*};
+}
+
+impl_channel!(u8, u16);
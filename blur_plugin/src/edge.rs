@@ -0,0 +1,193 @@
+//! Обработка пикселей за границей изображения
+//!
+//! Все режимы применяются по каждой оси отдельно, поэтому одинаково
+//! подходят и для сепарабельных ядер, и для квадратного окна.
+
+use plugin_sdk::{PluginError, Result};
+use serde::Deserialize;
+
+/// Наибольший радиус ядра для режимов, отличных от [`EdgeMode::Shrink`].
+///
+/// В остальных режимах окно за границей не обрезается, и радиус ограничен,
+/// чтобы суммы окна из `u16`-каналов гарантированно помещались в `u64`.
+pub const MAX_RADIUS: usize = 1 << 20;
+
+/// Что подставляется вместо пикселя за границей изображения.
+#[derive(Deserialize, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeMode {
+    /// Пиксель пропускается, ядро обрезается и перенормируется.
+    #[default]
+    Shrink,
+    /// Берётся ближайший пиксель края: `a a | a b c | c c`.
+    Clamp,
+    /// Отражение без повтора крайнего пикселя: `c b | a b c | b a`.
+    #[serde(alias = "reflect")]
+    Mirror,
+    /// Изображение повторяется периодически: `b c | a b c | a b`.
+    Wrap,
+    /// Пиксель считается прозрачным чёрным `(0, 0, 0, 0)`.
+    Transparent,
+}
+
+/// Результат обращения к индексу вдоль оси длины `len`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sample {
+    /// Значение пикселя с этим индексом.
+    Pixel(usize),
+    /// Нулевое значение, которое учитывается в весе ядра.
+    Zero,
+    /// Ничего: позиция не учитывается и в весе ядра.
+    Skip,
+}
+
+impl EdgeMode {
+    /// Во что превращается индекс `i` на оси длины `len > 0`.
+    pub fn sample(self, i: i64, len: usize) -> Sample {
+        let last = len as i64 - 1;
+        if (0..=last).contains(&i) {
+            return Sample::Pixel(i as usize);
+        }
+        match self {
+            Self::Shrink => Sample::Skip,
+            Self::Transparent => Sample::Zero,
+            Self::Clamp => Sample::Pixel(i.clamp(0, last) as usize),
+            Self::Wrap => Sample::Pixel(i.rem_euclid(len as i64) as usize),
+            Self::Mirror if len == 1 => Sample::Pixel(0),
+            Self::Mirror => {
+                let period = 2 * last;
+                let i = i.rem_euclid(period);
+                Sample::Pixel(if i <= last { i } else { period - i } as usize)
+            }
+        }
+    }
+
+    /// Сколько раз пиксель `k` попадает в окно `-radius..=radius` вокруг
+    /// индекса `center` оси длины `len`.
+    pub fn multiplicity(self, k: usize, center: usize, radius: usize, len: usize) -> u64 {
+        let (k, last) = (k as i64, len as i64 - 1);
+        let (low, high) = (center as i64 - radius as i64, center as i64 + radius as i64);
+        let inside = u64::from((low..=high).contains(&k));
+        let count = match self {
+            Self::Shrink | Self::Transparent => return inside,
+            _ if len == 1 => high - low + 1,
+            Self::Clamp if k == 0 => (high.min(0) - low + 1).max(0),
+            Self::Clamp if k == last => (high - low.max(last) + 1).max(0),
+            Self::Clamp => return inside,
+            Self::Wrap => congruent(k, low, high, len as i64),
+            Self::Mirror => {
+                let period = 2 * last;
+                let reflected = if k == 0 || k == last {
+                    0
+                } else {
+                    congruent(period - k, low, high, period)
+                };
+                congruent(k, low, high, period) + reflected
+            }
+        };
+        count as u64
+    }
+
+    /// Сколько позиций окна `-radius..=radius` вокруг `i` учитывается в весе
+    /// ядра.
+    pub fn window_len(self, i: usize, radius: usize, len: usize) -> u64 {
+        match self {
+            Self::Shrink => {
+                (i.saturating_add(radius).min(len - 1) - i.saturating_sub(radius) + 1) as u64
+            }
+            _ => 2 * radius as u64 + 1,
+        }
+    }
+
+    /// Радиус, с которым ядро на оси длины `len` даёт тот же результат, что
+    /// и `radius`; для режимов без обрезки проверяет [`MAX_RADIUS`].
+    pub fn effective_radius(self, radius: usize, len: usize) -> Result<usize> {
+        match self {
+            Self::Shrink => Ok(radius.min(len)),
+            _ if radius <= MAX_RADIUS => Ok(radius),
+            _ => Err(PluginError::invalid_params(format!(
+                "радиус {radius} больше допустимого {MAX_RADIUS} для режима краёв {self:?}"
+            ))),
+        }
+    }
+}
+
+/// Количество `j` в `low..=high`, сравнимых с `k` по модулю `period`.
+fn congruent(k: i64, low: i64, high: i64, period: i64) -> i64 {
+    (high - k).div_euclid(period) - (low - 1 - k).div_euclid(period)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Индексы, в которые превращается отрезок `from..to` оси длины `len`
+    fn line(mode: EdgeMode, from: i64, to: i64, len: usize) -> Vec<Option<usize>> {
+        (from..to)
+            .map(|i| match mode.sample(i, len) {
+                Sample::Pixel(k) => Some(k),
+                Sample::Zero | Sample::Skip => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sample_patterns() {
+        assert_eq!(
+            line(EdgeMode::Clamp, -2, 5, 3),
+            [0, 0, 0, 1, 2, 2, 2].map(Some)
+        );
+        assert_eq!(
+            line(EdgeMode::Mirror, -4, 7, 3),
+            [0, 1, 2, 1, 0, 1, 2, 1, 0, 1, 2].map(Some)
+        );
+        assert_eq!(
+            line(EdgeMode::Wrap, -4, 5, 3),
+            [2, 0, 1, 2, 0, 1, 2, 0, 1].map(Some)
+        );
+        assert_eq!(line(EdgeMode::Mirror, -2, 3, 1), [Some(0); 5]);
+        assert_eq!(EdgeMode::Shrink.sample(-1, 3), Sample::Skip);
+        assert_eq!(EdgeMode::Transparent.sample(3, 3), Sample::Zero);
+    }
+
+    #[test]
+    fn test_multiplicity_matches_sampling() {
+        let modes = [
+            EdgeMode::Shrink,
+            EdgeMode::Clamp,
+            EdgeMode::Mirror,
+            EdgeMode::Wrap,
+            EdgeMode::Transparent,
+        ];
+        for mode in modes {
+            for len in 1..6 {
+                for radius in 0..13 {
+                    for center in 0..len {
+                        let window = center as i64 - radius as i64..=(center + radius) as i64;
+                        for k in 0..len {
+                            let expected = window
+                                .clone()
+                                .filter(|&i| mode.sample(i, len) == Sample::Pixel(k))
+                                .count() as u64;
+                            assert_eq!(
+                                mode.multiplicity(k, center, radius, len),
+                                expected,
+                                "{mode:?}, len {len}, radius {radius}, center {center}, k {k}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_effective_radius() {
+        assert_eq!(EdgeMode::Shrink.effective_radius(usize::MAX, 7), Ok(7));
+        assert_eq!(EdgeMode::Wrap.effective_radius(100, 7), Ok(100));
+        let error = EdgeMode::Clamp
+            .effective_radius(MAX_RADIUS + 1, 7)
+            .unwrap_err();
+        assert_eq!(error.code(), plugin_sdk::ERROR_INVALID_PARAMS);
+    }
+}
@@ -0,0 +1,249 @@
+//! Размытие по Гауссу
+//!
+//! Ядро сепарабельно: сначала каждая строка сворачивается с одномерным ядром
+//! по горизонтали, затем результат — по вертикали. Ядро обрезается на `3σ`,
+//! а за краями изображения пиксели достраиваются по [`EdgeMode`]; веса
+//! пропущенных пикселей исключаются, и ядро перенормируется.
+
+use crate::channel::Channel;
+use crate::edge::{EdgeMode, Sample};
+use crate::parallel::{Checkpoint, RowCounter, for_each_band};
+use plugin_sdk::{CHANNELS, PluginError, Result};
+
+/// Половина ширины ядра для `sigma`.
+pub fn kernel_radius(sigma: f32) -> usize {
+    // Преобразование насыщающее: огромная sigma даёт usize::MAX
+    (3.0 * sigma).ceil() as usize
+}
+
+/// Одномерное ядро `exp(-x² / 2σ²)` для `x` в `-radius..=radius`,
+/// нормированное на единичную сумму.
+pub fn kernel(sigma: f32, radius: usize) -> Vec<f32> {
+    let denominator = 2.0 * sigma * sigma;
+    let mut weights: Vec<f32> = (0..=2 * radius)
+        .map(|i| {
+            let x = i as f32 - radius as f32;
+            (-x * x / denominator).exp()
+        })
+        .collect();
+    let total: f32 = weights.iter().sum();
+    for weight in &mut weights {
+        *weight /= total;
+    }
+    weights
+}
+
+/// Проверяет, что `sigma` — положительное конечное число.
+pub fn check_sigma(sigma: f32) -> Result<()> {
+    if sigma > 0.0 && sigma.is_finite() {
+        Ok(())
+    } else {
+        Err(PluginError::invalid_params(format!(
+            "sigma должна быть положительной, получено {sigma}"
+        )))
+    }
+}
+
+/// Свёртка в точке `i` ряда длины `len` с ядром `weights`; `read(k)`
+/// возвращает `CHANNELS` каналов `k`-го элемента.
+fn convolve(
+    weights: &[f32],
+    len: usize,
+    i: usize,
+    edge: EdgeMode,
+    read: impl Fn(usize) -> [f32; CHANNELS],
+) -> [f32; CHANNELS] {
+    let radius = weights.len() / 2;
+    let mut acc = [0f32; CHANNELS];
+    let mut total = 0.0;
+    for (offset, &weight) in weights.iter().enumerate() {
+        match edge.sample((i + offset) as i64 - radius as i64, len) {
+            Sample::Pixel(k) => {
+                let value = read(k);
+                for c in 0..CHANNELS {
+                    acc[c] += weight * value[c];
+                }
+            }
+            Sample::Zero => {}
+            Sample::Skip => continue,
+        }
+        total += weight;
+    }
+    // Перенормировка на учтённую часть ядра
+    acc.map(|v| v / total)
+}
+
+/// Один проход размытия `src` в `dst` (оба — RGBA `width × height` с
+/// каналами `T`) в `threads` потоках.
+///
+/// Проход выполняется в две фазы: все строки размываются по горизонтали в
+/// промежуточный буфер `f32`, затем по вертикали в `dst`; внутри фазы строки
+/// делятся между потоками. `checkpoint` вызывается перед каждой строкой
+/// каждой фазы с долей готовой работы.
+#[allow(clippy::too_many_arguments)]
+pub fn gaussian_blur<T: Channel>(
+    src: &[T],
+    dst: &mut [T],
+    width: usize,
+    height: usize,
+    sigma: f32,
+    edge: EdgeMode,
+    threads: usize,
+    checkpoint: Checkpoint<'_>,
+) -> Result<()> {
+    check_sigma(sigma)?;
+    if width == 0 || height == 0 {
+        return Ok(());
+    }
+    let radius = edge.effective_radius(kernel_radius(sigma), width.max(height))?;
+    let weights = kernel(sigma, radius);
+    let row_len = width * CHANNELS;
+    let progress = RowCounter::new(2 * height, checkpoint);
+
+    let mut rows = vec![0f32; height * row_len];
+    for_each_band(&mut rows, row_len, threads, |band_rows, band| {
+        for (y, target) in band_rows.zip(band.chunks_exact_mut(row_len)) {
+            progress.tick()?;
+            let source = &src[y * row_len..(y + 1) * row_len];
+            for x in 0..width {
+                let value = convolve(&weights, width, x, edge, |k| {
+                    std::array::from_fn(|c| source[k * CHANNELS + c].to_f32())
+                });
+                target[x * CHANNELS..(x + 1) * CHANNELS].copy_from_slice(&value);
+            }
+        }
+        Ok(())
+    })?;
+
+    let rows = &rows;
+    for_each_band(dst, row_len, threads, |band_rows, band| {
+        for (y, out) in band_rows.zip(band.chunks_exact_mut(row_len)) {
+            progress.tick()?;
+            for x in 0..width {
+                let value = convolve(&weights, height, y, edge, |k| {
+                    let start = k * row_len + x * CHANNELS;
+                    std::array::from_fn(|c| rows[start + c])
+                });
+                for c in 0..CHANNELS {
+                    out[x * CHANNELS + c] = T::from_f32(value[c]);
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blur(src: &[u8], width: usize, height: usize, sigma: f32) -> Vec<u8> {
+        let mut dst = vec![0; src.len()];
+        let edge = EdgeMode::Shrink;
+        gaussian_blur(src, &mut dst, width, height, sigma, edge, 1, &|_| Ok(())).unwrap();
+        dst
+    }
+
+    #[test]
+    fn test_kernel_is_normalized_and_symmetric() {
+        for sigma in [0.3, 1.0, 2.5, 10.0] {
+            let radius = kernel_radius(sigma);
+            let weights = kernel(sigma, radius);
+            assert_eq!(weights.len(), 2 * radius + 1);
+            assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+            for i in 0..radius {
+                assert_eq!(weights[i], weights[2 * radius - i]);
+                assert!(weights[i] < weights[i + 1]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_flat_image_unchanged() {
+        // Перенормировка у краёв: однотонное изображение не темнеет к границам
+        let src: Vec<u8> = [10, 100, 200, 255].repeat(7 * 5);
+        assert_eq!(blur(&src, 7, 5, 2.0), src);
+    }
+
+    #[test]
+    fn test_impulse_response() {
+        // Одиночная точка расплывается в симметричное пятно с максимумом в центре
+        let (width, height) = (9, 9);
+        let mut src = vec![0u8; width * height * CHANNELS];
+        let center = (4 * width + 4) * CHANNELS;
+        src[center] = 255;
+
+        let dst = blur(&src, width, height, 1.0);
+        let red = |x: usize, y: usize| dst[(y * width + x) * CHANNELS];
+
+        assert_eq!(red(4, 3), red(4, 5));
+        assert_eq!(red(3, 4), red(5, 4));
+        assert_eq!(red(3, 3), red(5, 5));
+        assert!(red(4, 4) > red(4, 3) && red(4, 3) > red(3, 3) && red(3, 3) > red(2, 2));
+        // Центральный вес двумерного ядра: (1 / Σ exp(-x²/2))² ≈ 0.1592
+        assert_eq!(red(4, 4), (255.0f32 * 0.1592).round() as u8);
+    }
+
+    #[test]
+    fn test_sigma_validated() {
+        let src = vec![0u8; 4];
+        let mut dst = vec![0u8; 4];
+        for sigma in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            let edge = EdgeMode::Shrink;
+            let error =
+                gaussian_blur(&src, &mut dst, 1, 1, sigma, edge, 1, &|_| Ok(())).unwrap_err();
+            assert_eq!(error.code(), plugin_sdk::ERROR_INVALID_PARAMS);
+        }
+    }
+
+    #[test]
+    fn test_edge_modes() {
+        let (width, height) = (6, 4);
+        let flat: Vec<u8> = [10, 100, 200, 255].repeat(width * height);
+        for edge in [EdgeMode::Clamp, EdgeMode::Mirror, EdgeMode::Wrap] {
+            let mut dst = vec![0; flat.len()];
+            gaussian_blur(&flat, &mut dst, width, height, 1.5, edge, 1, &|_| Ok(())).unwrap();
+            assert_eq!(dst, flat, "{edge:?}");
+        }
+
+        // Прозрачная рамка вокруг изображения: край теряет альфу сильнее центра
+        let mut dst = vec![0; flat.len()];
+        let edge = EdgeMode::Transparent;
+        gaussian_blur(&flat, &mut dst, width, height, 1.0, edge, 1, &|_| Ok(())).unwrap();
+        let alpha = |x: usize, y: usize| dst[(y * width + x) * CHANNELS + 3];
+        assert!(alpha(0, 0) < alpha(0, 1) && alpha(0, 1) < alpha(2, 1));
+        assert!(alpha(2, 1) < 255);
+
+        // Вертикальная полоса у левого края при wrap переносится на правый
+        let mut src = vec![0u8; width * height * CHANNELS];
+        for y in 0..height {
+            src[y * width * CHANNELS] = 255;
+        }
+        let mut dst = vec![0; src.len()];
+        let edge = EdgeMode::Wrap;
+        gaussian_blur(&src, &mut dst, width, height, 1.0, edge, 1, &|_| Ok(())).unwrap();
+        let red = |x: usize| dst[(2 * width + x) * CHANNELS];
+        assert_eq!(red(1), red(width - 1));
+        assert!(red(width - 1) > red(2));
+    }
+
+    #[test]
+    fn test_threads_match_single_thread() {
+        let (width, height) = (11, 9);
+        let src: Vec<u8> = (0..width * height * CHANNELS)
+            .map(|i| (i * 97 % 251) as u8)
+            .collect();
+        for edge in [EdgeMode::Shrink, EdgeMode::Mirror, EdgeMode::Wrap] {
+            let mut single = vec![0; src.len()];
+            gaussian_blur(&src, &mut single, width, height, 2.0, edge, 1, &|_| Ok(())).unwrap();
+            for threads in [2, 3, 16] {
+                let mut multi = vec![0; src.len()];
+                gaussian_blur(&src, &mut multi, width, height, 2.0, edge, threads, &|_| {
+                    Ok(())
+                })
+                .unwrap();
+                assert_eq!(multi, single, "{edge:?}, {threads} потоков");
+            }
+        }
+    }
+}
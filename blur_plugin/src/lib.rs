@@ -2,141 +2,480 @@
 
 #![warn(missing_docs)]
 
+pub mod alpha;
+pub mod bilateral;
+pub mod box_blur;
+pub mod channel;
+pub mod edge;
+pub mod gaussian;
+pub mod linear;
+pub mod mask;
+pub mod parallel;
+pub mod segment;
+pub mod tilt_shift;
+pub mod unsharp;
+
+use bilateral::bilateral_blur;
+use box_blur::box_blur;
+use channel::Channel;
+use edge::EdgeMode;
+use gaussian::gaussian_blur;
+use mask::Region;
+use plugin_sdk::{Context, ImagePlugin, ImageView, Result};
+use segment::{motion_blur, radial_blur};
 use serde::Deserialize;
 use std::ffi::CStr;
-use std::os::raw::c_char;
+use std::path::PathBuf;
+use tilt_shift::{Focus, FocusShape, tilt_shift_blur};
+use unsharp::unsharp_mask;
+
+/// JSON Schema параметров плагина.
+const PARAMS_SCHEMA: &CStr = cr#"{
+    "type": "object",
+    "properties": {
+        "mode": {
+            "type": "string",
+            "enum": ["box", "gaussian", "motion", "radial", "bilateral", "tilt_shift", "unsharp"],
+            "description": "Ядро размытия: квадратное усреднение, гауссово, размытие движением, радиальное (zoom), билатеральное с сохранением границ, tilt-shift с резкой зоной или повышение резкости нерезкой маской"
+        },
+        "radius": {
+            "type": "integer",
+            "minimum": 0,
+            "description": "Радиус квадратной области усреднения в пикселях (mode = box)"
+        },
+        "sigma": {
+            "type": "number",
+            "exclusiveMinimum": 0,
+            "description": "Стандартное отклонение гауссова ядра в пикселях (mode = gaussian, unsharp)"
+        },
+        "angle": {
+            "type": "number",
+            "description": "Направление движения в градусах против часовой стрелки от горизонтали (mode = motion)"
+        },
+        "length": {
+            "type": "number",
+            "minimum": 0,
+            "description": "Длина следа движения в пикселях (mode = motion)"
+        },
+        "center": {
+            "type": "array",
+            "items": {"type": "number", "minimum": 0, "maximum": 1},
+            "minItems": 2,
+            "maxItems": 2,
+            "description": "Центр радиального размытия [x, y] в долях ширины и высоты (mode = radial)"
+        },
+        "strength": {
+            "type": "number",
+            "minimum": 0,
+            "maximum": 1,
+            "description": "Доля расстояния до центра, на которую тянется след (mode = radial)"
+        },
+        "sigma_spatial": {
+            "type": "number",
+            "minimum": 1,
+            "description": "Пространственное стандартное отклонение в пикселях (mode = bilateral)"
+        },
+        "sigma_range": {
+            "type": "number",
+            "exclusiveMinimum": 0,
+            "description": "Отклонение по яркости в долях полной шкалы: перепады заметно больше него не размываются (mode = bilateral)"
+        },
+        "focus": {
+            "type": "string",
+            "enum": ["linear", "radial"],
+            "description": "Форма резкой зоны: полоса или круг (mode = tilt_shift)"
+        },
+        "focus_center": {
+            "type": "array",
+            "items": {"type": "number", "minimum": 0, "maximum": 1},
+            "minItems": 2,
+            "maxItems": 2,
+            "description": "Центр резкой зоны [x, y] в долях ширины и высоты (mode = tilt_shift)"
+        },
+        "focus_angle": {
+            "type": "number",
+            "description": "Наклон резкой полосы в градусах против часовой стрелки от горизонтали (mode = tilt_shift)"
+        },
+        "focus_width": {
+            "type": "number",
+            "minimum": 0,
+            "description": "Ширина резкой полосы или диаметр круга в долях меньшей стороны изображения (mode = tilt_shift)"
+        },
+        "falloff": {
+            "type": "number",
+            "minimum": 0,
+            "description": "Расстояние от резкой зоны, на котором размытие дорастает до max_radius, в долях меньшей стороны (mode = tilt_shift)"
+        },
+        "max_radius": {
+            "type": "integer",
+            "minimum": 0,
+            "description": "Наибольший радиус размытия вдали от резкой зоны в пикселях (mode = tilt_shift)"
+        },
+        "amount": {
+            "type": "number",
+            "minimum": 0,
+            "description": "Сила повышения резкости: во сколько раз усиливается отличие от размытого (mode = unsharp)"
+        },
+        "threshold": {
+            "type": "number",
+            "minimum": 0,
+            "maximum": 1,
+            "description": "Отличия от размытого меньше этой доли полной шкалы не усиливаются (mode = unsharp)"
+        },
+        "iterations": {
+            "type": "integer",
+            "minimum": 0,
+            "description": "Количество проходов размытия"
+        },
+        "edge_mode": {
+            "type": "string",
+            "enum": ["shrink", "clamp", "mirror", "reflect", "wrap", "transparent"],
+            "description": "Чем достраивать изображение за краем: обрезать ядро, повторять крайний пиксель, отражать, повторять изображение или считать прозрачным"
+        },
+        "premultiplied": {
+            "type": "boolean",
+            "description": "Размывать цвет, умноженный на альфу, чтобы прозрачные пиксели не окрашивали соседние"
+        },
+        "linear": {
+            "type": "boolean",
+            "description": "Размывать в линейном свете, а не в гамма-сжатых значениях sRGB"
+        },
+        "regions": {
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "x": {"type": "integer", "minimum": 0},
+                    "y": {"type": "integer", "minimum": 0},
+                    "width": {"type": "integer", "minimum": 0},
+                    "height": {"type": "integer", "minimum": 0}
+                },
+                "required": ["x", "y", "width", "height"],
+                "additionalProperties": false
+            },
+            "description": "Размывать только эти прямоугольники в пикселях"
+        },
+        "mask": {
+            "type": "string",
+            "format": "path",
+            "description": "Путь к полутоновой маске размером с изображение: белое размывается полностью, чёрное остаётся как есть. Относительный путь в файле конвейера отсчитывается от его каталога, иначе — от текущего каталога"
+        },
+        "feather": {
+            "type": "number",
+            "minimum": 0,
+            "description": "Ширина растушёвки краёв прямоугольников regions в пикселях"
+        },
+        "threads": {
+            "type": "integer",
+            "minimum": 1,
+            "description": "Число потоков (не больше числа строк изображения); по умолчанию — все доступные ядра"
+        }
+    },
+    "additionalProperties": false
+}"#;
+
+plugin_sdk::export_plugin! {
+    plugin: Blur,
+    name: c"blur_plugin",
+    description: c"Размытие изображения: квадратное усреднение, по Гауссу, движением, радиальное, билатеральное или tilt-shift; повышение резкости нерезкой маской",
+    params_schema: PARAMS_SCHEMA,
+}
+
+/// Ядро размытия.
+#[derive(Deserialize, Default, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Mode {
+    /// Среднее по квадрату `(2 * radius + 1) × (2 * radius + 1)`.
+    #[default]
+    Box,
+    /// Сепарабельное гауссово ядро с отклонением `sigma`.
+    Gaussian,
+    /// След движения длиной `length` под углом `angle`.
+    Motion,
+    /// След к центру `center` длиной `strength` от расстояния до него.
+    Radial,
+    /// Билатеральный фильтр: сглаживание с сохранением границ.
+    Bilateral,
+    /// Радиус растёт от резкой зоны `focus` до `max_radius`.
+    #[serde(rename = "tilt_shift")]
+    TiltShift,
+    /// Повышение резкости нерезкой маской на гауссовом ядре `sigma`.
+    Unsharp,
+}
 
+/// Параметры `{"mode": "box" | "gaussian" | "motion" | "radial" | "bilateral" |
+/// "tilt_shift" | "unsharp", "radius": u32, "sigma": f32, "angle": f32, "length": f32,
+/// "center": [f32; 2], "strength": f32, "sigma_spatial": f32, "sigma_range": f32,
+/// "focus": "linear" | "radial", "focus_center": [f32; 2], "focus_angle": f32,
+/// "focus_width": f32, "falloff": f32, "max_radius": u32, "amount": f32,
+/// "threshold": f32, "iterations": u32,
+/// "edge_mode": "shrink" | "clamp" | "mirror" | "wrap" | "transparent",
+/// "premultiplied": bool, "linear": bool, "regions": [{"x": u32, "y": u32,
+/// "width": u32, "height": u32}], "mask": "путь", "feather": f32, "threads": u32}`.
+///
+/// Отсутствующие поля принимают значения по умолчанию: `box`, радиус 1,
+/// `sigma` 1.0, горизонтальный след в 10 пикселей, радиальный след к
+/// середине изображения в 0.2 расстояния, билатеральный фильтр с `sigma_spatial`
+/// 8 и `sigma_range` 0.1, горизонтальная резкая полоса tilt-shift через
+/// середину шириной 0.2 со спадом 0.3 до радиуса 12, резкость с `amount` 1.0
+/// без порога, один проход, ядро обрезается у краёв, размывается
+/// премультиплицированный цвет в sRGB всего изображения на всех доступных ядрах.
 #[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
 struct Params {
+    mode: Mode,
     radius: u32,
+    sigma: f32,
+    angle: f32,
+    length: f32,
+    center: [f32; 2],
+    strength: f32,
+    sigma_spatial: f32,
+    sigma_range: f32,
+    focus: FocusShape,
+    focus_center: [f32; 2],
+    focus_angle: f32,
+    focus_width: f32,
+    falloff: f32,
+    max_radius: u32,
+    amount: f32,
+    threshold: f32,
     iterations: u32,
+    edge_mode: EdgeMode,
+    premultiplied: bool,
+    linear: bool,
+    regions: Vec<Region>,
+    mask: Option<PathBuf>,
+    feather: f32,
+    threads: Option<u32>,
 }
 
-/// Применяет эффект размытия к изображению на месте.
-///
-/// # Safety
-/// 
-/// Алгоритм выполняет `iterations` проходов размытия с радиусом `radius`.
-/// Каждый пиксель заменяется средним значением пикселей в квадратной области
-/// размером `(2 * radius + 1) × (2 * radius + 1)`.
-///
-/// # Параметры
-///
-/// - `width`, `height`: размеры изображения в пикселях
-/// - `data`: указатель на буфер в формате RGBA8 (4 байта на пиксель)
-/// - `params`: JSON-строка с параметрами `{"radius": u32, "iterations": u32}`
-///   или null (используются значения по умолчанию: radius=1, iterations=1)
-///
-/// # Безопасность
-///
-/// Эта функция является FFI-границей. Вызывающая сторона ОБЯЗАНА гарантировать:
-///
-/// - `data` указывает на валидный, изменяемый буфер размером не менее
-///   `width × height × 4` байт в формате RGBA8;
-/// - буфер правильно выровнен и остаётся валидным на время выполнения;
-/// - `params` — либо null, либо корректная нуль-терминированная C-строка;
-/// - функция не вызывается конкурентно для одного и того же буфера.
+impl Params {
+    /// Резкая зона режима `tilt_shift`.
+    fn focus(&self) -> Focus {
+        Focus {
+            shape: self.focus,
+            center: self.focus_center,
+            angle: self.focus_angle,
+            width: self.focus_width,
+            falloff: self.falloff,
+        }
+    }
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Box,
+            radius: 1,
+            sigma: 1.0,
+            angle: 0.0,
+            length: 10.0,
+            center: [0.5, 0.5],
+            strength: 0.2,
+            sigma_spatial: 8.0,
+            sigma_range: 0.1,
+            focus: FocusShape::Linear,
+            focus_center: [0.5, 0.5],
+            focus_angle: 0.0,
+            focus_width: 0.2,
+            falloff: 0.3,
+            max_radius: 12,
+            amount: 1.0,
+            threshold: 0.0,
+            iterations: 1,
+            edge_mode: EdgeMode::Shrink,
+            premultiplied: true,
+            linear: false,
+            regions: Vec::new(),
+            mask: None,
+            feather: 0.0,
+            threads: None,
+        }
+    }
+}
+
+/// Размытие изображения на месте.
 ///
-/// # Возврат
+/// Алгоритм выполняет `iterations` проходов размытия. В режиме `box` каждый
+/// пиксель заменяется средним значением пикселей в квадратной области
+/// размером `(2 * radius + 1) × (2 * radius + 1)`, в режиме `gaussian` —
+/// взвешенным средним с гауссовыми весами, в режимах `motion` и `radial` —
+/// средним вдоль отрезка (см. [`segment`]), в режиме `bilateral` — средним
+/// только по пикселям близкой яркости (см. [`bilateral`]), в режиме
+/// `tilt_shift` — квадратным средним, радиус которого растёт от резкой зоны
+/// (см. [`tilt_shift`]). Режим `unsharp`, наоборот, повышает резкость,
+/// усиливая отличие от гауссова размытия (см. [`unsharp`]). Пиксели за краем изображения
+/// достраиваются по `edge_mode`, по умолчанию область обрезается границами.
+/// Сложность прохода `box` не зависит от радиуса, см. [`box_blur`].
 ///
-/// - `0` — успешно обработано;
-/// - `-1` — ошибка (переполнение арифметики, невалидные параметры, слишком большой размер изображения, повреждённая память).
+/// С `premultiplied` размывается цвет, умноженный на альфу (см. [`alpha`]);
+/// у непрозрачных изображений результат от этого не меняется. С `linear`
+/// изображение перед размытием раскодируется из sRGB в линейный свет (см.
+/// [`linear`]), и светлые детали не тускнеют.
 ///
-/// # Предупреждения безопасности
+/// С `regions` и/или `mask` размытый результат смешивается с исходником по
+/// весам пикселей (см. [`mask`]), и пиксели вне областей не меняются.
 ///
-/// При переполнении при вычислении размера буфера (`width × height × 4`)
-/// в релизной сборке произойдёт wrapping, что приведёт к созданию среза
-/// с некорректной длиной и неопределённому поведению (UB) при работе
-/// с `unsafe`-кодом. Поэтому используется проверенная арифметика через
-/// `.checked_mul()`.
-#[unsafe(no_mangle)]
-pub unsafe extern "C" fn process_image(
-    width: u32,
-    height: u32,
-    data: *mut u8,
-    params: *const c_char,
-) -> i32 {
-    let total_pixels = match width.checked_mul(height) {
-        Some(v) => v,
-        None => return -1,
-    };
-
-    let len = total_pixels.saturating_mul(4);
-
-    if len > 0 && data.is_null() {
-        return -1;
-    }
-    let len_usize: usize = match len.try_into() {
-        Ok(v) => v,
-        Err(_) => return -1,
-    };
-    let w: isize = match width.try_into() {
-        Ok(v) => v,
-        Err(_) => return -1, // width > i32::MAX
-    };
-    let h: isize = match height.try_into() {
-        Ok(v) => v,
-        Err(_) => return -1, // height > i32::MAX
-    };
-
-    // Создание среза из сырых данных
-    let buf = unsafe { std::slice::from_raw_parts_mut(data, len_usize) };
-    let params_str = if params.is_null() {
-        ""
-    } else {
-        match unsafe { CStr::from_ptr(params) }.to_str() {
-            Ok(s) => s,
-            Err(_) => return -1,
+/// Строки делятся между `threads` потоками; результат от их числа не
+/// зависит.
+struct Blur;
+
+impl ImagePlugin for Blur {
+    type Params = Params;
+
+    fn process(image: &mut ImageView<'_>, params: Params, context: &Context<'_>) -> Result<()> {
+        match params.mode {
+            Mode::Box => {}
+            Mode::Gaussian => gaussian::check_sigma(params.sigma)?,
+            Mode::Motion => segment::check_length(params.length)?,
+            Mode::Radial => segment::check_radial(params.center, params.strength)?,
+            Mode::Bilateral => bilateral::check_sigmas(params.sigma_spatial, params.sigma_range)?,
+            Mode::TiltShift => params.focus().check()?,
+            Mode::Unsharp => {
+                gaussian::check_sigma(params.sigma)?;
+                unsharp::check_unsharp(params.amount, params.threshold)?
+            }
         }
-    };
+        mask::check_feather(params.feather)?;
 
-    let params: Params = match serde_json::from_str(params_str) {
-        Ok(p) => p,
-        Err(_) => Params {
-            radius: 1,
-            iterations: 1,
-        },
-    };
+        let Some(weights) = weights(image, &params)? else {
+            return blur_image(image, &params, context);
+        };
+        if weights.iter().all(|&weight| weight == 0.0) {
+            return Ok(());
+        }
+        let original = image.as_bytes().to_vec();
+        blur_image(image, &params, context)?;
+        mask::blend(&original, image.as_bytes_mut(), &weights);
+        Ok(())
+    }
+}
+
+/// Веса пикселей из `regions` и `mask` (наибольший из двух) или `None`,
+/// если размывается всё изображение.
+fn weights(image: &ImageView<'_>, params: &Params) -> Result<Option<Vec<f32>>> {
+    let (w, h) = image.dimensions();
+    let regions = (!params.regions.is_empty())
+        .then(|| mask::region_weights(&params.regions, params.feather, w, h));
+    let mask = params
+        .mask
+        .as_deref()
+        .map(|path| mask::load_mask(path, w, h))
+        .transpose()?;
+    Ok(match (regions, mask) {
+        (Some(mut regions), Some(mask)) => {
+            for (weight, masked) in regions.iter_mut().zip(mask) {
+                *weight = weight.max(masked);
+            }
+            Some(regions)
+        }
+        (regions, mask) => regions.or(mask),
+    })
+}
+
+/// Размывает всё изображение в цветовом пространстве, заданном `linear` и
+/// `premultiplied`.
+fn blur_image(image: &mut ImageView<'_>, params: &Params, context: &Context<'_>) -> Result<()> {
+    let (w, h) = image.dimensions();
+    if params.linear {
+        let mut working = linear::to_working(&image.to_linear(), params.premultiplied);
+        blur(&mut working, w, h, params, context)?;
+        image.store_linear(&linear::from_working(&working, params.premultiplied));
+        return Ok(());
+    }
+
+    let buf = image.as_bytes_mut();
+    if params.premultiplied {
+        let mut premultiplied = alpha::premultiply(buf);
+        blur(&mut premultiplied, w, h, params, context)?;
+        alpha::unpremultiply(&premultiplied, buf, params.mode != Mode::Box);
+    } else {
+        blur(buf, w, h, params, context)?;
+    }
+    Ok(())
+}
 
+/// Выполняет все проходы размытия над буфером RGBA `w × h`.
+fn blur<T: Channel>(
+    buf: &mut [T],
+    w: usize,
+    h: usize,
+    params: &Params,
+    context: &Context<'_>,
+) -> Result<()> {
+    let edge = params.edge_mode;
+    let threads = parallel::threads(params.threads);
     let mut temp = buf.to_vec();
-    let radius = params.radius as isize;
-
-    for _ in 0..params.iterations {
-        for y in 0..h {
-            for x in 0..w {
-                let mut sum = [0u32; 4];
-                let mut count = 0;
-
-                for dy in -radius..=radius {
-                    for dx in -radius..=radius {
-                        let nx = x + dx;
-                        let ny = y + dy;
-
-                        if nx >= 0 && nx < w && ny >= 0 && ny < h {
-                            // Все значения неотрицательны → безопасное преобразование в usize
-                            let idx = ((ny * w + nx) * 4) as usize;
-                            for c in 0..4 {
-                                sum[c] += temp[idx + c] as u32;
-                            }
-                            count += 1;
-                        }
-                    }
-                }
-                let dst = ((y * w + x) * 4) as usize;
-                for c in 0..4 {
-                    buf[dst + c] = (sum[c] / count) as u8;
-                }
+
+    let iterations = params.iterations as f32;
+    for iteration in 0..params.iterations {
+        let stage = format!("проход {}/{}", iteration + 1, params.iterations);
+        let checkpoint =
+            |fraction| context.checkpoint((iteration as f32 + fraction) / iterations, &stage);
+        match params.mode {
+            Mode::Box => {
+                let radius = params.radius as usize;
+                box_blur(&temp, buf, w, h, radius, edge, threads, &checkpoint)?
+            }
+            Mode::Gaussian => {
+                gaussian_blur(&temp, buf, w, h, params.sigma, edge, threads, &checkpoint)?
+            }
+            Mode::Motion => {
+                let (angle, length) = (params.angle, params.length);
+                motion_blur(&temp, buf, w, h, angle, length, edge, threads, &checkpoint)?
+            }
+            Mode::Radial => {
+                let (center, strength) = (params.center, params.strength);
+                radial_blur(
+                    &temp,
+                    buf,
+                    w,
+                    h,
+                    center,
+                    strength,
+                    edge,
+                    threads,
+                    &checkpoint,
+                )?
+            }
+            Mode::Bilateral => {
+                let (spatial, range) = (params.sigma_spatial, params.sigma_range);
+                bilateral_blur(&temp, buf, w, h, spatial, range, edge, threads, &checkpoint)?
+            }
+            Mode::TiltShift => {
+                let (focus, radius) = (params.focus(), params.max_radius as usize);
+                tilt_shift_blur(&temp, buf, w, h, &focus, radius, edge, threads, &checkpoint)?
+            }
+            Mode::Unsharp => {
+                let (sigma, amount, threshold) = (params.sigma, params.amount, params.threshold);
+                unsharp_mask(
+                    &temp,
+                    buf,
+                    w,
+                    h,
+                    sigma,
+                    amount,
+                    threshold,
+                    params.premultiplied,
+                    edge,
+                    threads,
+                    &checkpoint,
+                )?
             }
         }
         temp.copy_from_slice(buf);
     }
-    0
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use plugin_sdk::abi::{ABI_VERSION, PIXEL_FORMAT_RGBA8};
+    use plugin_sdk::{ERROR_INVALID_PARAMS, ERROR_NULL_POINTER};
     use std::ffi::CString;
+    use std::os::raw::c_char;
 
     /// Вспомогательная функция для безопасного вызова FFI-функции из тестов
     unsafe fn call_process_image(
@@ -150,7 +489,15 @@ mod tests {
             None => std::ptr::null(),
         };
 
-        let result = unsafe { process_image(width, height, data.as_mut_ptr(), params_ptr) };
+        let result = unsafe {
+            process_image(
+                width,
+                height,
+                data.as_mut_ptr(),
+                params_ptr,
+                std::ptr::null(),
+            )
+        };
 
         // Освобождаем память CString, если она была создана
         if !params_ptr.is_null() {
@@ -189,8 +536,15 @@ mod tests {
         // Передаём максимальные значения — функция должна вернуть ошибку, а не паниковать
         let mut dummy = [0u8; 4];
 
-        let result =
-            unsafe { process_image(u32::MAX, u32::MAX, dummy.as_mut_ptr(), std::ptr::null()) };
+        let result = unsafe {
+            process_image(
+                u32::MAX,
+                u32::MAX,
+                dummy.as_mut_ptr(),
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        };
 
         assert_eq!(
             result, -1,
@@ -202,11 +556,511 @@ mod tests {
         // Пустое изображение (0×0) — корректный случай, не должен вызывать ошибок
         let mut dummy = Vec::<u8>::new();
 
-        let result = unsafe { process_image(0, 0, dummy.as_mut_ptr(), std::ptr::null()) };
+        let result =
+            unsafe { process_image(0, 0, dummy.as_mut_ptr(), std::ptr::null(), std::ptr::null()) };
 
         assert_eq!(
             result, 0,
             "Пустое изображение (0×0) должно обрабатываться без ошибок"
         );
     }
+
+    #[test]
+    fn test_descriptor() {
+        // Дескриптор должен сообщать текущую версию ABI и имя плагина
+        let descriptor = unsafe { &*plugin_descriptor() };
+
+        assert_eq!(descriptor.abi_version, ABI_VERSION);
+        assert_eq!(
+            descriptor.pixel_formats & PIXEL_FORMAT_RGBA8,
+            PIXEL_FORMAT_RGBA8
+        );
+        assert_eq!(
+            unsafe { CStr::from_ptr(descriptor.name) }.to_str(),
+            Ok("blur_plugin")
+        );
+        assert_eq!(
+            unsafe { CStr::from_ptr(descriptor.version) }.to_str(),
+            Ok(env!("CARGO_PKG_VERSION"))
+        );
+    }
+
+    #[test]
+    fn test_params_schema() {
+        // Схема параметров должна быть корректным JSON и описывать все поля `Params`
+        let schema: serde_json::Value =
+            serde_json::from_str(PARAMS_SCHEMA.to_str().unwrap()).unwrap();
+
+        assert_eq!(schema["type"], "object");
+        assert!(schema["properties"]["radius"].is_object());
+        assert!(schema["properties"]["iterations"].is_object());
+        assert!(schema["properties"]["sigma"].is_object());
+        assert_eq!(schema["properties"]["mode"]["enum"][1], "gaussian");
+        for field in ["angle", "length", "center", "strength"] {
+            assert!(schema["properties"][field].is_object(), "{field}");
+        }
+        assert!(schema["properties"]["edge_mode"].is_object());
+        assert!(schema["properties"]["premultiplied"].is_object());
+        assert!(schema["properties"]["linear"].is_object());
+        assert!(schema["properties"]["threads"].is_object());
+        // Хост разрешает относительный путь маски от каталога файла конвейера
+        assert_eq!(schema["properties"]["mask"]["format"], "path");
+    }
+
+    #[test]
+    fn test_invalid_params_reported() {
+        // Опечатка в параметрах — ошибка с кодом и текстом, а не тихие значения по умолчанию
+        let mut data = vec![255, 0, 0, 255];
+        let result = unsafe { call_process_image(1, 1, &mut data, Some(r#"{"radus": 2}"#)) };
+
+        assert_eq!(result, ERROR_INVALID_PARAMS);
+        let message = unsafe { CStr::from_ptr(plugin_last_error()) }
+            .to_str()
+            .unwrap();
+        assert!(message.contains("невалидные параметры"), "{message}");
+
+        // Успешный вызов сбрасывает сообщение
+        let result = unsafe {
+            call_process_image(1, 1, &mut data, Some(r#"{"radius": 2, "iterations": 3}"#))
+        };
+        assert_eq!(result, 0);
+        assert!(unsafe { CStr::from_ptr(plugin_last_error()) }.is_empty());
+    }
+
+    #[test]
+    fn test_null_data_reported() {
+        let result = unsafe {
+            process_image(
+                2,
+                2,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(result, ERROR_NULL_POINTER);
+    }
+
+    #[test]
+    fn test_cancelled_by_host() {
+        // Хост, запросивший отмену, получает ERROR_CANCELLED уже на первой строке
+        unsafe extern "C" fn cancelled(_: *mut std::ffi::c_void) -> i32 {
+            1
+        }
+        let context = plugin_sdk::abi::HostContext {
+            user_data: std::ptr::null_mut(),
+            report_progress: None,
+            is_cancelled: Some(cancelled),
+        };
+        let mut data = vec![255; 4 * 4 * 4];
+
+        let result = unsafe { process_image(4, 4, data.as_mut_ptr(), std::ptr::null(), &context) };
+
+        assert_eq!(result, plugin_sdk::ERROR_CANCELLED);
+    }
+
+    #[test]
+    fn test_gaussian_mode() {
+        // Вертикальная граница чёрное/белое сглаживается монотонным переходом
+        let (width, height) = (8, 3);
+        let mut data: Vec<u8> = (0..width * height)
+            .flat_map(|i| {
+                if i % width < 4 {
+                    [0, 0, 0, 255]
+                } else {
+                    [255, 255, 255, 255]
+                }
+            })
+            .collect();
+        let result = unsafe {
+            call_process_image(
+                width as u32,
+                height as u32,
+                &mut data,
+                Some(r#"{"mode": "gaussian", "sigma": 1.5}"#),
+            )
+        };
+        assert_eq!(result, 0);
+
+        let row: Vec<u8> = data[..width * 4].chunks_exact(4).map(|p| p[0]).collect();
+        assert!(row.windows(2).all(|pair| pair[0] < pair[1]), "{row:?}");
+        // Переход симметричен относительно границы
+        assert_eq!(u16::from(row[3]) + u16::from(row[4]), 255, "{row:?}");
+        assert!(data.chunks_exact(4).all(|p| p[3] == 255));
+    }
+
+    #[test]
+    fn test_gaussian_invalid_sigma() {
+        let mut data = vec![255, 0, 0, 255];
+        let result = unsafe {
+            call_process_image(1, 1, &mut data, Some(r#"{"mode": "gaussian", "sigma": 0}"#))
+        };
+        assert_eq!(result, ERROR_INVALID_PARAMS);
+
+        let result = unsafe { call_process_image(1, 1, &mut data, Some(r#"{"mode": "median"}"#)) };
+        assert_eq!(result, ERROR_INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_edge_mode_param() {
+        // Горизонтальный градиент: clamp и wrap по-разному меняют левый край
+        let row = [0, 0, 0, 255, 30, 0, 0, 255, 90, 0, 0, 255];
+        for (edge_mode, left) in [("shrink", 15), ("clamp", 10), ("reflect", 20), ("wrap", 40)] {
+            let mut data = row.to_vec();
+            let params = format!(r#"{{"radius": 1, "edge_mode": "{edge_mode}"}}"#);
+            let result = unsafe { call_process_image(3, 1, &mut data, Some(&params)) };
+            assert_eq!(result, 0);
+            assert_eq!(data[0], left, "{edge_mode}");
+        }
+    }
+
+    /// Полоса 4×1: два полупрозрачных красных пикселя и два полностью
+    /// прозрачных, чей RGB — ярко-зелёный
+    fn half_transparent_edge() -> Vec<u8> {
+        [
+            [255, 0, 0, 128],
+            [255, 0, 0, 128],
+            [0, 255, 0, 0],
+            [0, 255, 0, 0],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_premultiplied_no_color_bleed() {
+        for mode in ["box", "gaussian"] {
+            let mut data = half_transparent_edge();
+            let params = format!(r#"{{"mode": "{mode}", "radius": 1, "sigma": 1}}"#);
+            let result = unsafe { call_process_image(4, 1, &mut data, Some(&params)) };
+            assert_eq!(result, 0);
+
+            for pixel in data.chunks_exact(4) {
+                // Зелёный прозрачных пикселей не просачивается, красный не темнеет
+                assert_eq!(pixel[1], 0, "{mode}: {data:?}");
+                if pixel[3] > 0 {
+                    assert_eq!(pixel[0], 255, "{mode}: {data:?}");
+                }
+            }
+            // Альфа по-прежнему размывается
+            assert!(
+                data[3] > data[7] && data[7] > data[11] && data[11] > 0,
+                "{data:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_straight_alpha_bleeds() {
+        // Без премультипликации цвет прозрачных пикселей смешивается с видимыми
+        let mut data = half_transparent_edge();
+        let params = r#"{"radius": 1, "premultiplied": false}"#;
+        let result = unsafe { call_process_image(4, 1, &mut data, Some(params)) };
+        assert_eq!(result, 0);
+
+        assert_eq!(&data[4..8], [170, 85, 0, 85]);
+    }
+
+    #[test]
+    fn test_premultiplied_opaque_matches_straight() {
+        // У непрозрачного изображения премультипликация не меняет результат
+        let source: Vec<u8> = (0..5 * 4)
+            .flat_map(|i| [(i * 37 % 256) as u8, (i * 11) as u8, 200, 255])
+            .collect();
+        for mode in ["box", "gaussian"] {
+            let mut results = Vec::new();
+            for premultiplied in [true, false] {
+                let mut data = source.clone();
+                let params = format!(
+                    r#"{{"mode": "{mode}", "radius": 2, "premultiplied": {premultiplied}}}"#
+                );
+                let result = unsafe { call_process_image(5, 4, &mut data, Some(&params)) };
+                assert_eq!(result, 0);
+                results.push(data);
+            }
+            assert_eq!(results[0], results[1], "{mode}");
+        }
+    }
+
+    #[test]
+    fn test_linear_reference() {
+        // Чёрный и белый пиксель усредняются в половину линейной яркости: 188 в sRGB
+        for premultiplied in [true, false] {
+            let mut data = vec![0, 0, 0, 255, 255, 255, 255, 255];
+            let params = format!(r#"{{"linear": true, "premultiplied": {premultiplied}}}"#);
+            let result = unsafe { call_process_image(2, 1, &mut data, Some(&params)) };
+            assert_eq!(result, 0);
+            assert_eq!(data, [188, 188, 188, 255, 188, 188, 188, 255]);
+        }
+
+        // В гамма-пространстве то же среднее темнее
+        let mut data = vec![0, 0, 0, 255, 255, 255, 255, 255];
+        let result = unsafe { call_process_image(2, 1, &mut data, Some("{}")) };
+        assert_eq!(result, 0);
+        assert_eq!(data, [127, 127, 127, 255, 127, 127, 127, 255]);
+    }
+
+    #[test]
+    fn test_linear_gaussian_reference() {
+        // Чистые красный и синий: каждый канал — взвешенное среднее в линейном
+        // свете; веса ядра sigma = 1 на краю 2×1 после перенормировки: 1 / (1 + e^-½)
+        let mut data = vec![255, 0, 0, 255, 0, 0, 255, 255];
+        let params = r#"{"mode": "gaussian", "sigma": 1, "linear": true}"#;
+        let result = unsafe { call_process_image(2, 1, &mut data, Some(params)) };
+        assert_eq!(result, 0);
+
+        let near = 1.0 / (1.0 + (-0.5f32).exp());
+        let strong = plugin_sdk::color::linear_to_srgb(near);
+        let weak = plugin_sdk::color::linear_to_srgb(1.0 - near);
+        assert_eq!(data, [strong, 0, weak, 255, weak, 0, strong, 255]);
+        assert_eq!((strong, weak), (207, 165));
+    }
+
+    #[test]
+    fn test_threads_param() {
+        // Результат не зависит от числа потоков ни в одном из путей обработки
+        let (width, height) = (23, 19);
+        let source: Vec<u8> = (0..width * height * 4)
+            .map(|i| (i * 131 % 253) as u8)
+            .collect();
+        let variants = [
+            r#""mode": "box", "radius": 3"#,
+            r#""mode": "gaussian", "sigma": 1.5, "edge_mode": "wrap""#,
+            r#""mode": "box", "radius": 2, "premultiplied": false, "iterations": 2"#,
+            r#""mode": "gaussian", "sigma": 2, "linear": true"#,
+            r#""mode": "motion", "angle": 30, "length": 6, "edge_mode": "mirror""#,
+            r#""mode": "radial", "center": [0.3, 0.6], "strength": 0.4"#,
+            r#""mode": "bilateral", "sigma_spatial": 2, "sigma_range": 0.2, "edge_mode": "clamp""#,
+            r#""mode": "tilt_shift", "focus": "radial", "max_radius": 5, "linear": true"#,
+            r#""mode": "unsharp", "sigma": 1.5, "amount": 2, "threshold": 0.02"#,
+        ];
+        for variant in variants {
+            let run = |threads: u32| {
+                let mut data = source.clone();
+                let params = format!(r#"{{{variant}, "threads": {threads}}}"#);
+                let result = unsafe {
+                    call_process_image(width as u32, height as u32, &mut data, Some(&params))
+                };
+                assert_eq!(result, 0, "{params}");
+                data
+            };
+            let single = run(1);
+            for threads in [2, 5, 32] {
+                assert_eq!(run(threads), single, "{variant}, {threads} потоков");
+            }
+        }
+    }
+
+    #[test]
+    fn test_motion_and_radial_share_alpha_treatment() {
+        // Полупрозрачный красный рядом с прозрачным зелёным: зелёный не
+        // просачивается ни в одном из новых режимов
+        for mode in [
+            r#""mode": "motion", "length": 2"#,
+            r#""mode": "radial", "center": [0, 0.5], "strength": 1"#,
+        ] {
+            let mut data = half_transparent_edge();
+            let params = format!("{{{mode}}}");
+            let result = unsafe { call_process_image(4, 1, &mut data, Some(&params)) };
+            assert_eq!(result, 0, "{mode}");
+            for pixel in data.chunks_exact(4).filter(|p| p[3] > 0) {
+                assert_eq!(pixel[..3], [255, 0, 0], "{mode}: {data:?}");
+            }
+            assert!(data[11] > 0, "{mode}: альфа размыта, {data:?}");
+        }
+    }
+
+    #[test]
+    fn test_radial_invalid_center() {
+        let mut data = vec![255, 0, 0, 255];
+        let params = r#"{"mode": "radial", "center": [0.5, 1.5]}"#;
+        let result = unsafe { call_process_image(1, 1, &mut data, Some(params)) };
+        assert_eq!(result, ERROR_INVALID_PARAMS);
+
+        let params = r#"{"mode": "radial", "center": [0.5]}"#;
+        let result = unsafe { call_process_image(1, 1, &mut data, Some(params)) };
+        assert_eq!(result, ERROR_INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_bilateral_keeps_edge() {
+        // Граница чёрное/белое: гауссово ядро её размывает, билатеральное — нет
+        let (width, height) = (16, 4);
+        let source: Vec<u8> = (0..width * height)
+            .flat_map(|i| {
+                if i % width < 8 {
+                    [0, 0, 0, 255]
+                } else {
+                    [255; 4]
+                }
+            })
+            .collect();
+        for (mode, expected) in [
+            (r#""mode": "bilateral", "sigma_spatial": 2"#, true),
+            (r#""mode": "gaussian", "sigma": 2"#, false),
+        ] {
+            let mut data = source.clone();
+            let params = format!("{{{mode}}}");
+            let result = unsafe {
+                call_process_image(width as u32, height as u32, &mut data, Some(&params))
+            };
+            assert_eq!(result, 0, "{mode}");
+            assert_eq!(data == source, expected, "{mode}: {data:?}");
+        }
+    }
+
+    #[test]
+    fn test_bilateral_invalid_sigmas() {
+        let mut data = vec![255, 0, 0, 255];
+        for params in [
+            r#"{"mode": "bilateral", "sigma_spatial": 0.5}"#,
+            r#"{"mode": "bilateral", "sigma_range": 0}"#,
+        ] {
+            let result = unsafe { call_process_image(1, 1, &mut data, Some(params)) };
+            assert_eq!(result, ERROR_INVALID_PARAMS, "{params}");
+        }
+    }
+
+    #[test]
+    fn test_regions_leave_outside_untouched() {
+        let (width, height) = (12, 8);
+        let source: Vec<u8> = (0..width * height * 4)
+            .map(|i| (i * 97 % 251) as u8)
+            .collect();
+        let params = r#"{"mode": "gaussian", "sigma": 2, "feather": 2,
+            "regions": [{"x": 2, "y": 1, "width": 5, "height": 4},
+                        {"x": 9, "y": 6, "width": 10, "height": 10}]}"#;
+        let mut data = source.clone();
+        let result =
+            unsafe { call_process_image(width as u32, height as u32, &mut data, Some(params)) };
+        assert_eq!(result, 0);
+
+        let inside =
+            |x: usize, y: usize| (2..7).contains(&x) && (1..5).contains(&y) || x >= 9 && y >= 6;
+        for (i, (before, after)) in source.chunks(4).zip(data.chunks(4)).enumerate() {
+            let (x, y) = (i % width, i / width);
+            if !inside(x, y) {
+                assert_eq!(before, after, "({x}, {y}) вне областей");
+            }
+        }
+        // Середина первого прямоугольника размыта полностью
+        let mut full = source.clone();
+        let params = r#"{"mode": "gaussian", "sigma": 2}"#;
+        unsafe { call_process_image(width as u32, height as u32, &mut full, Some(params)) };
+        let center = (2 * width + 4) * 4;
+        assert_eq!(data[center..center + 4], full[center..center + 4]);
+        assert_ne!(data[center..center + 4], source[center..center + 4]);
+    }
+
+    #[test]
+    fn test_regions_outside_image_change_nothing() {
+        let mut data: Vec<u8> = (0..4 * 4 * 4).map(|i| (i * 37 % 256) as u8).collect();
+        let source = data.clone();
+        let params = r#"{"regions": [{"x": 10, "y": 0, "width": 5, "height": 5}]}"#;
+        let result = unsafe { call_process_image(4, 4, &mut data, Some(params)) };
+        assert_eq!(result, 0);
+        assert_eq!(data, source);
+    }
+
+    #[test]
+    fn test_mask_file() {
+        // Маска 4×1: левая половина чёрная, правая белая
+        let path = std::env::temp_dir().join(format!("blur_lib_mask_{}.png", std::process::id()));
+        image::GrayImage::from_raw(4, 1, vec![0, 0, 255, 255])
+            .unwrap()
+            .save(&path)
+            .unwrap();
+        let row = [0, 0, 0, 255, 90, 0, 0, 255, 30, 0, 0, 255, 0, 0, 0, 255];
+        let mut data = row.to_vec();
+        let params = format!(r#"{{"mask": {:?}}}"#, path.to_str().unwrap());
+        let result = unsafe { call_process_image(4, 1, &mut data, Some(&params)) };
+
+        let mut missing = row.to_vec();
+        let bad = r#"{"mask": "/несуществующая/маска.png"}"#;
+        let missing_result = unsafe { call_process_image(4, 1, &mut missing, Some(bad)) };
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(result, 0);
+        assert_eq!(data[..8], row[..8]);
+        assert_eq!([data[8], data[12]], [40, 15]);
+        assert_eq!(missing_result, ERROR_INVALID_PARAMS);
+        assert_eq!(missing, row);
+    }
+
+    #[test]
+    fn test_tilt_shift_focus_band() {
+        // Горизонтальная полоса через середину остаётся резкой, края размыты
+        let (width, height) = (20, 30);
+        let source: Vec<u8> = (0..width * height * 4)
+            .map(|i| {
+                if i % 4 == 3 {
+                    255
+                } else {
+                    (i * 89 % 256) as u8
+                }
+            })
+            .collect();
+        let mut data = source.clone();
+        let params =
+            r#"{"mode": "tilt_shift", "focus_width": 0.3, "falloff": 0.4, "max_radius": 4}"#;
+        let result =
+            unsafe { call_process_image(width as u32, height as u32, &mut data, Some(params)) };
+        assert_eq!(result, 0);
+
+        let row = |data: &[u8], y: usize| data[y * width * 4..(y + 1) * width * 4].to_vec();
+        for y in 12..=17 {
+            assert_eq!(row(&data, y), row(&source, y), "строка {y}");
+        }
+        assert_ne!(row(&data, 0), row(&source, 0));
+        assert_ne!(row(&data, 29), row(&source, 29));
+    }
+
+    #[test]
+    fn test_tilt_shift_invalid_focus() {
+        let mut data = vec![255, 0, 0, 255];
+        for params in [
+            r#"{"mode": "tilt_shift", "focus_center": [2, 0.5]}"#,
+            r#"{"mode": "tilt_shift", "falloff": -1}"#,
+            r#"{"mode": "tilt_shift", "focus": "diagonal"}"#,
+        ] {
+            let result = unsafe { call_process_image(1, 1, &mut data, Some(params)) };
+            assert_eq!(result, ERROR_INVALID_PARAMS, "{params}");
+        }
+    }
+
+    #[test]
+    fn test_unsharp_mode() {
+        // Граница чёрное/белое становится контрастнее только у самой границы,
+        // шум ниже порога на ровном участке не усиливается
+        let (width, height) = (12, 3);
+        let source: Vec<u8> = (0..width * height)
+            .flat_map(|i| {
+                let x = i % width;
+                let value = if x < 6 { 40 + (x as u8 % 2) * 4 } else { 200 };
+                [value, value, value, 255]
+            })
+            .collect();
+        let mut data = source.clone();
+        let params = r#"{"mode": "unsharp", "sigma": 1, "amount": 1.5, "threshold": 0.04}"#;
+        let result =
+            unsafe { call_process_image(width as u32, height as u32, &mut data, Some(params)) };
+        assert_eq!(result, 0);
+
+        let row: Vec<u8> = data[..width * 4].chunks_exact(4).map(|p| p[0]).collect();
+        assert!(row[5] < 40 && row[6] > 200, "{row:?}");
+        assert_eq!(row[..3], [40, 44, 40], "{row:?}");
+        assert!(data.chunks_exact(4).all(|p| p[3] == 255));
+    }
+
+    #[test]
+    fn test_unsharp_invalid_params() {
+        let mut data = vec![255, 0, 0, 255];
+        for params in [
+            r#"{"mode": "unsharp", "amount": -1}"#,
+            r#"{"mode": "unsharp", "threshold": 2}"#,
+            r#"{"mode": "unsharp", "sigma": 0}"#,
+        ] {
+            let result = unsafe { call_process_image(1, 1, &mut data, Some(params)) };
+            assert_eq!(result, ERROR_INVALID_PARAMS, "{params}");
+        }
+    }
 }
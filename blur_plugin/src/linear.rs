@@ -0,0 +1,77 @@
+//! Рабочий буфер для размытия в линейном свете
+//!
+//! Ядра размытия работают с целочисленными каналами, поэтому линейные
+//! значения от SDK ([`plugin_sdk::color`]) переводятся в `u16`: 16 бит
+//! хватает, чтобы тёмные тона не слипались после обратного кодирования в
+//! sRGB. С `premultiplied` цвет дополнительно умножается на альфу.
+
+use plugin_sdk::CHANNELS;
+
+const SCALE: f32 = u16::MAX as f32;
+
+/// Линейные RGBA `f32` → `u16`, при `premultiplied` цвет умножается на альфу.
+pub fn to_working(linear: &[f32], premultiplied: bool) -> Vec<u16> {
+    linear
+        .chunks_exact(CHANNELS)
+        .flat_map(|pixel| {
+            let alpha = pixel[3];
+            let weight = if premultiplied { alpha } else { 1.0 };
+            let quantize = |value: f32| (value * SCALE).round().clamp(0.0, SCALE) as u16;
+            [
+                quantize(pixel[0] * weight),
+                quantize(pixel[1] * weight),
+                quantize(pixel[2] * weight),
+                quantize(alpha),
+            ]
+        })
+        .collect()
+}
+
+/// Обратное к [`to_working`] преобразование; цвет полностью прозрачного
+/// премультиплицированного пикселя — чёрный.
+pub fn from_working(working: &[u16], premultiplied: bool) -> Vec<f32> {
+    working
+        .chunks_exact(CHANNELS)
+        .flat_map(|pixel| {
+            let alpha = f32::from(pixel[3]);
+            let divisor = match (premultiplied, pixel[3]) {
+                (false, _) => SCALE,
+                (true, 0) => f32::INFINITY,
+                (true, _) => alpha,
+            };
+            [
+                f32::from(pixel[0]) / divisor,
+                f32::from(pixel[1]) / divisor,
+                f32::from(pixel[2]) / divisor,
+                alpha / SCALE,
+            ]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let linear = [
+            0.0, 0.25, 1.0, 1.0, 0.5, 0.75, 0.125, 0.5, 0.3, 0.6, 0.9, 0.0,
+        ];
+        for premultiplied in [false, true] {
+            let back = from_working(&to_working(&linear, premultiplied), premultiplied);
+            for (i, (&before, &after)) in linear.iter().zip(&back).enumerate() {
+                // Цвет полностью прозрачного пикселя теряется только с премультипликацией
+                let expected = if premultiplied && (8..11).contains(&i) {
+                    0.0
+                } else {
+                    before
+                };
+                assert!(
+                    (after - expected).abs() < 1e-4,
+                    "{i}: {after} != {expected}"
+                );
+            }
+        }
+    }
+}
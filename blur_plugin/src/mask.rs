@@ -0,0 +1,173 @@
+//! Размытие только части изображения
+//!
+//! Области задаются списком прямоугольников и/или полутоновой маской того
+//! же размера, что и изображение. Из них получается вес каждого пикселя от
+//! 0 до 1, и результат смешивается с исходником: `исходник + (размытое -
+//! исходник) * вес`. Пиксели с весом 0 не меняются совсем.
+
+use plugin_sdk::{CHANNELS, PluginError, Result};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Прямоугольник `width × height` с левым верхним углом `(x, y)` в пикселях.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Region {
+    /// Левый край.
+    pub x: u32,
+    /// Верхний край.
+    pub y: u32,
+    /// Ширина.
+    pub width: u32,
+    /// Высота.
+    pub height: u32,
+}
+
+/// Проверяет ширину растушёвки.
+pub fn check_feather(feather: f32) -> Result<()> {
+    if feather >= 0.0 && feather.is_finite() {
+        Ok(())
+    } else {
+        Err(PluginError::invalid_params(format!(
+            "feather должна быть неотрицательной, получено {feather}"
+        )))
+    }
+}
+
+/// Веса пикселей изображения `width × height` для прямоугольников `regions`.
+///
+/// Вес растёт линейно от края прямоугольника внутрь и достигает 1 на
+/// глубине `feather` пикселей; снаружи всех прямоугольников он равен 0, в
+/// пересечениях берётся наибольший.
+pub fn region_weights(regions: &[Region], feather: f32, width: usize, height: usize) -> Vec<f32> {
+    let mut weights = vec![0f32; width * height];
+    for region in regions {
+        let (left, top) = (region.x as usize, region.y as usize);
+        let right = (left + region.width as usize).min(width);
+        let bottom = (top + region.height as usize).min(height);
+        let (end_x, end_y) = (left + region.width as usize, top + region.height as usize);
+        for y in top..bottom {
+            for x in left..right {
+                // Глубина пикселя внутри прямоугольника: 1 у самого края
+                let depth = (x - left + 1)
+                    .min(end_x - x)
+                    .min(y - top + 1)
+                    .min(end_y - y);
+                let weight = if feather > 0.0 {
+                    (depth as f32 / feather).min(1.0)
+                } else {
+                    1.0
+                };
+                let cell = &mut weights[y * width + x];
+                *cell = cell.max(weight);
+            }
+        }
+    }
+    weights
+}
+
+/// Веса из полутоновой маски `path`: чёрное — 0, белое — 1.
+///
+/// Цветные маски переводятся в яркость; размер маски должен совпадать с
+/// размером изображения.
+pub fn load_mask(path: &Path, width: usize, height: usize) -> Result<Vec<f32>> {
+    let mask = image::open(path)
+        .map_err(|e| {
+            PluginError::invalid_params(format!("маска {} не читается: {e}", path.display()))
+        })?
+        .to_luma8();
+    let (mask_width, mask_height) = mask.dimensions();
+    if (mask_width as usize, mask_height as usize) != (width, height) {
+        return Err(PluginError::invalid_params(format!(
+            "маска {} размером {mask_width}×{mask_height}, а изображение {width}×{height}",
+            path.display()
+        )));
+    }
+    Ok(mask.pixels().map(|p| f32::from(p.0[0]) / 255.0).collect())
+}
+
+/// Смешивает размытый RGBA8 `blurred` с исходным `original` по весам
+/// пикселей `weights`, результат — в `blurred`.
+pub fn blend(original: &[u8], blurred: &mut [u8], weights: &[f32]) {
+    let pixels = original
+        .chunks_exact(CHANNELS)
+        .zip(blurred.chunks_exact_mut(CHANNELS));
+    for ((source, pixel), &weight) in pixels.zip(weights) {
+        for (&from, to) in source.iter().zip(pixel) {
+            let (from_value, to_value) = (f32::from(from), f32::from(*to));
+            *to = (from_value + (to_value - from_value) * weight).round() as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(x: u32, y: u32, width: u32, height: u32) -> Region {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn test_region_weights() {
+        let weights = region_weights(&[region(1, 0, 3, 2)], 0.0, 5, 3);
+        #[rustfmt::skip]
+        assert_eq!(weights, [
+            0.0, 1.0, 1.0, 1.0, 0.0,
+            0.0, 1.0, 1.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 0.0, 0.0,
+        ]);
+    }
+
+    #[test]
+    fn test_feather_and_overlap() {
+        // Растушёвка на 2 пикселя: у края 0.5, глубже 1; средняя строка
+        let weights = region_weights(&[region(0, 0, 6, 5)], 2.0, 8, 5);
+        assert_eq!(weights[16..24], [0.5, 1.0, 1.0, 1.0, 1.0, 0.5, 0.0, 0.0]);
+        assert_eq!(weights[..8], [0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 0.0, 0.0]);
+
+        // В пересечении берётся наибольший вес; прямоугольник за краем обрезается
+        let weights = region_weights(&[region(0, 0, 2, 5), region(1, 0, 9, 5)], 2.0, 4, 5);
+        assert_eq!(weights[8..12], [0.5, 0.5, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_blend() {
+        let original = [10, 20, 30, 255, 10, 20, 30, 255, 10, 20, 30, 255];
+        let mut blurred = [110, 120, 130, 55, 110, 120, 130, 55, 110, 120, 130, 55];
+        blend(&original, &mut blurred, &[0.0, 0.5, 1.0]);
+        assert_eq!(
+            blurred,
+            [10, 20, 30, 255, 60, 70, 80, 155, 110, 120, 130, 55]
+        );
+    }
+
+    #[test]
+    fn test_load_mask() {
+        let path = std::env::temp_dir().join(format!("blur_mask_{}.png", std::process::id()));
+        image::GrayImage::from_raw(3, 1, vec![0, 51, 255])
+            .unwrap()
+            .save(&path)
+            .unwrap();
+
+        assert_eq!(load_mask(&path, 3, 1), Ok(vec![0.0, 0.2, 1.0]));
+        let error = load_mask(&path, 2, 2).unwrap_err();
+        assert_eq!(error.code(), plugin_sdk::ERROR_INVALID_PARAMS);
+        std::fs::remove_file(&path).unwrap();
+
+        let error = load_mask(&path, 3, 1).unwrap_err();
+        assert_eq!(error.code(), plugin_sdk::ERROR_INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_check_feather() {
+        assert!(check_feather(0.0).is_ok());
+        assert!(check_feather(-1.0).is_err());
+        assert!(check_feather(f32::NAN).is_err());
+    }
+}
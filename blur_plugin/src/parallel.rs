@@ -0,0 +1,145 @@
+//! Распределение строк изображения по потокам
+//!
+//! Буфер делится на полосы подряд идущих строк, каждая обрабатывается в
+//! своём потоке. Ядра вычисляют каждую строку одинаково независимо от
+//! разбиения, поэтому результат не зависит от числа потоков.
+
+use plugin_sdk::Result;
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// Обратный вызов с долей выполненной работы прохода от 0 до 1; ошибка
+/// прерывает проход.
+pub type Checkpoint<'a> = &'a (dyn Fn(f32) -> Result<()> + Sync);
+
+/// Число потоков: `requested` или, если не задано, число доступных ядер.
+///
+/// Больше потоков, чем строк, [`for_each_band`] всё равно не запускает.
+pub fn threads(requested: Option<u32>) -> usize {
+    match requested {
+        Some(threads) => (threads as usize).max(1),
+        None => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+    }
+}
+
+/// Вызывает `job(rows, band)` для полос строк буфера `data` (строки по
+/// `row_len` элементов) не более чем в `threads` потоках.
+///
+/// Возвращает первую ошибку из полос; паника в полосе продолжается в
+/// вызывающем потоке.
+pub fn for_each_band<T: Send>(
+    data: &mut [T],
+    row_len: usize,
+    threads: usize,
+    job: impl Fn(Range<usize>, &mut [T]) -> Result<()> + Sync,
+) -> Result<()> {
+    let height = data.len().checked_div(row_len).unwrap_or(0);
+    let bands = threads.clamp(1, height.max(1));
+    if bands == 1 {
+        return job(0..height, data);
+    }
+
+    let band_rows = height.div_ceil(bands);
+    let job = &job;
+    thread::scope(|scope| {
+        let handles: Vec<_> = data
+            .chunks_mut(band_rows * row_len)
+            .enumerate()
+            .map(|(i, band)| {
+                let start = i * band_rows;
+                let rows = start..start + band.len() / row_len;
+                scope.spawn(move || job(rows, band))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect::<Result<Vec<()>>>()
+            .map(drop)
+    })
+}
+
+/// Счётчик обработанных строк прохода, общий для всех потоков.
+pub struct RowCounter<'a> {
+    done: AtomicUsize,
+    total: usize,
+    checkpoint: Checkpoint<'a>,
+}
+
+impl<'a> RowCounter<'a> {
+    /// Счётчик для прохода из `total` строк.
+    pub fn new(total: usize, checkpoint: Checkpoint<'a>) -> Self {
+        Self {
+            done: AtomicUsize::new(0),
+            total,
+            checkpoint,
+        }
+    }
+
+    /// Вызывается перед каждой строкой: сообщает долю уже готовых строк.
+    pub fn tick(&self) -> Result<()> {
+        let done = self.done.fetch_add(1, Ordering::Relaxed);
+        (self.checkpoint)(done as f32 / self.total.max(1) as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_sdk::PluginError;
+
+    #[test]
+    fn test_threads_requested_as_is() {
+        // Явное число потоков не урезается до числа ядер, иначе на
+        // одноядерной машине многопоточный путь не проверялся бы вовсе
+        assert_eq!(threads(Some(32)), 32);
+        assert_eq!(threads(Some(0)), 1);
+        assert!(threads(None) >= 1);
+    }
+
+    #[test]
+    fn test_bands_cover_all_rows() {
+        for threads in [1, 2, 3, 7, 100] {
+            let mut data = vec![0usize; 10 * 3];
+            for_each_band(&mut data, 3, threads, |rows, band| {
+                assert_eq!(band.len(), rows.len() * 3);
+                for (y, row) in rows.zip(band.chunks_mut(3)) {
+                    row.fill(y);
+                }
+                Ok(())
+            })
+            .unwrap();
+            let expected: Vec<usize> = (0..10).flat_map(|y| [y; 3]).collect();
+            assert_eq!(data, expected, "{threads} потоков");
+        }
+    }
+
+    #[test]
+    fn test_error_from_band() {
+        let mut data = vec![0u8; 8];
+        let result = for_each_band(&mut data, 1, 4, |rows, _| {
+            if rows.contains(&5) {
+                Err(PluginError::cancelled())
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!(result, Err(PluginError::cancelled()));
+    }
+
+    #[test]
+    fn test_empty_buffer() {
+        let mut data: Vec<u8> = Vec::new();
+        for_each_band(&mut data, 0, 4, |rows, _| {
+            assert!(rows.is_empty());
+            Ok(())
+        })
+        .unwrap();
+    }
+}
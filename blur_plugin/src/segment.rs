@@ -0,0 +1,373 @@
+//! Размытие вдоль отрезков: motion и radial
+//!
+//! Каждый пиксель результата — среднее равномерно расставленных на отрезке
+//! выборок исходного изображения. Выборки берутся билинейной интерполяцией
+//! (центр пикселя `(x, y)` лежит в точке `(x, y)`), а соседи за краем
+//! изображения достраиваются по [`EdgeMode`]. У motion blur отрезок один и
+//! тот же для всех пикселей, у radial — направлен к центру и тем длиннее,
+//! чем дальше пиксель от центра.
+
+use crate::channel::Channel;
+use crate::edge::{EdgeMode, MAX_RADIUS, Sample};
+use crate::parallel::{Checkpoint, RowCounter, for_each_band};
+use plugin_sdk::{CHANNELS, PluginError, Result};
+
+/// Точка на плоскости изображения в пикселях.
+type Point = (f32, f32);
+
+/// Изображение-источник выборок.
+struct Source<'a, T> {
+    data: &'a [T],
+    width: usize,
+    height: usize,
+    edge: EdgeMode,
+}
+
+impl<T: Channel> Source<'_, T> {
+    /// Прибавляет к `acc` билинейную выборку в точке `(x, y)` и возвращает
+    /// её учтённый вес: пропущенные за краем соседи в нём не участвуют.
+    fn sample(&self, (x, y): Point, acc: &mut [f32; CHANNELS]) -> f32 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let mut total = 0.0;
+        for (dy, wy) in [(0, 1.0 - fy), (1, fy)] {
+            for (dx, wx) in [(0, 1.0 - fx), (1, fx)] {
+                let weight = wx * wy;
+                if weight == 0.0 {
+                    continue;
+                }
+                let sx = self.edge.sample(x0 as i64 + dx, self.width);
+                let sy = self.edge.sample(y0 as i64 + dy, self.height);
+                match (sx, sy) {
+                    (Sample::Pixel(px), Sample::Pixel(py)) => {
+                        let offset = (py * self.width + px) * CHANNELS;
+                        let pixel = &self.data[offset..offset + CHANNELS];
+                        for (acc, value) in acc.iter_mut().zip(pixel) {
+                            *acc += weight * value.to_f32();
+                        }
+                    }
+                    (Sample::Skip, _) | (_, Sample::Skip) => continue,
+                    _ => {}
+                }
+                total += weight;
+            }
+        }
+        total
+    }
+
+    /// Среднее `taps` выборок, равномерно расставленных от `from` до `to`.
+    fn average(&self, from: Point, to: Point, taps: usize) -> Option<[f32; CHANNELS]> {
+        let mut acc = [0f32; CHANNELS];
+        let mut total = 0.0;
+        for i in 0..taps {
+            let t = if taps == 1 {
+                0.0
+            } else {
+                i as f32 / (taps - 1) as f32
+            };
+            let point = (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+            total += self.sample(point, &mut acc);
+        }
+        (total > 0.0).then(|| acc.map(|v| v / total))
+    }
+}
+
+/// Число выборок на отрезке длины `length`: не реже одной на пиксель.
+fn taps(length: f32) -> usize {
+    length.ceil() as usize + 1
+}
+
+/// Размывает каждый пиксель вдоль отрезка, который возвращает `segment`.
+///
+/// Если все выборки отрезка оказались за краем (`shrink`), пиксель
+/// остаётся исходным.
+#[allow(clippy::too_many_arguments)]
+fn blur_segments<T: Channel>(
+    src: &[T],
+    dst: &mut [T],
+    width: usize,
+    height: usize,
+    edge: EdgeMode,
+    threads: usize,
+    checkpoint: Checkpoint<'_>,
+    segment: impl Fn(Point) -> (Point, Point, usize) + Sync,
+) -> Result<()> {
+    if width == 0 || height == 0 {
+        return Ok(());
+    }
+    let source = Source {
+        data: src,
+        width,
+        height,
+        edge,
+    };
+    let row_len = width * CHANNELS;
+    let progress = RowCounter::new(height, checkpoint);
+
+    for_each_band(dst, row_len, threads, |rows, band| {
+        for (y, out) in rows.zip(band.chunks_exact_mut(row_len)) {
+            progress.tick()?;
+            for x in 0..width {
+                let (from, to, taps) = segment((x as f32, y as f32));
+                let pixel = &mut out[x * CHANNELS..(x + 1) * CHANNELS];
+                match source.average(from, to, taps) {
+                    Some(value) => {
+                        for (out, value) in pixel.iter_mut().zip(value) {
+                            *out = T::from_f32(value);
+                        }
+                    }
+                    None => pixel.copy_from_slice(&src[(y * width + x) * CHANNELS..][..CHANNELS]),
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Проверяет длину отрезка motion blur.
+pub fn check_length(length: f32) -> Result<()> {
+    if (0.0..=MAX_RADIUS as f32).contains(&length) {
+        Ok(())
+    } else {
+        Err(PluginError::invalid_params(format!(
+            "length должна быть от 0 до {MAX_RADIUS}, получено {length}"
+        )))
+    }
+}
+
+/// Проверяет силу radial blur и центр в долях изображения.
+pub fn check_radial(center: [f32; 2], strength: f32) -> Result<()> {
+    if !(0.0..=1.0).contains(&strength) {
+        return Err(PluginError::invalid_params(format!(
+            "strength должна быть от 0 до 1, получено {strength}"
+        )));
+    }
+    if !center.iter().all(|c| (0.0..=1.0).contains(c)) {
+        return Err(PluginError::invalid_params(format!(
+            "center задаётся в долях изображения от 0 до 1, получено {center:?}"
+        )));
+    }
+    Ok(())
+}
+
+/// Размытие движением: отрезок длины `length` пикселей с центром в пикселе,
+/// повёрнутый на `angle` градусов против часовой стрелки от горизонтали.
+#[allow(clippy::too_many_arguments)]
+pub fn motion_blur<T: Channel>(
+    src: &[T],
+    dst: &mut [T],
+    width: usize,
+    height: usize,
+    angle: f32,
+    length: f32,
+    edge: EdgeMode,
+    threads: usize,
+    checkpoint: Checkpoint<'_>,
+) -> Result<()> {
+    check_length(length)?;
+    let (sin, cos) = angle.to_radians().sin_cos();
+    // Ось y изображения направлена вниз
+    let half = (cos * length / 2.0, -sin * length / 2.0);
+    let taps = taps(length);
+    blur_segments(
+        src,
+        dst,
+        width,
+        height,
+        edge,
+        threads,
+        checkpoint,
+        |(x, y)| ((x - half.0, y - half.1), (x + half.0, y + half.1), taps),
+    )
+}
+
+/// Радиальное (zoom) размытие: отрезок от пикселя к центру `center` (в долях
+/// ширины и высоты) длиной `strength` от расстояния до центра.
+#[allow(clippy::too_many_arguments)]
+pub fn radial_blur<T: Channel>(
+    src: &[T],
+    dst: &mut [T],
+    width: usize,
+    height: usize,
+    center: [f32; 2],
+    strength: f32,
+    edge: EdgeMode,
+    threads: usize,
+    checkpoint: Checkpoint<'_>,
+) -> Result<()> {
+    check_radial(center, strength)?;
+    // Центр в координатах центров пикселей
+    let cx = center[0] * width as f32 - 0.5;
+    let cy = center[1] * height as f32 - 0.5;
+    blur_segments(
+        src,
+        dst,
+        width,
+        height,
+        edge,
+        threads,
+        checkpoint,
+        |(x, y)| {
+            let to = (x + (cx - x) * strength, y + (cy - y) * strength);
+            let length = (to.0 - x).hypot(to.1 - y);
+            ((x, y), to, taps(length))
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: usize = 17;
+    const H: usize = 9;
+
+    /// Чёрное непрозрачное изображение `W × H` с белыми пикселями `points`
+    fn image(points: &[(usize, usize)]) -> Vec<u8> {
+        let mut data = [0, 0, 0, 255].repeat(W * H);
+        for &(x, y) in points {
+            data[(y * W + x) * CHANNELS..][..3].fill(255);
+        }
+        data
+    }
+
+    fn red(data: &[u8], x: usize, y: usize) -> u8 {
+        data[(y * W + x) * CHANNELS]
+    }
+
+    fn motion(src: &[u8], angle: f32, length: f32, edge: EdgeMode) -> Vec<u8> {
+        let mut dst = vec![0; src.len()];
+        motion_blur(src, &mut dst, W, H, angle, length, edge, 1, &|_| Ok(())).unwrap();
+        dst
+    }
+
+    fn radial(src: &[u8], center: [f32; 2], strength: f32) -> Vec<u8> {
+        let mut dst = vec![0; src.len()];
+        let edge = EdgeMode::Shrink;
+        radial_blur(src, &mut dst, W, H, center, strength, edge, 1, &|_| Ok(())).unwrap();
+        dst
+    }
+
+    #[test]
+    fn test_motion_direction() {
+        // Вертикальная линия: горизонтальное движение её размазывает,
+        // вертикальное — нет
+        let src = image(&(0..H).map(|y| (8, y)).collect::<Vec<_>>());
+
+        let horizontal = motion(&src, 0.0, 4.0, EdgeMode::Shrink);
+        for y in 0..H {
+            assert_eq!(red(&horizontal, 8, y), 51, "5 выборок, одна из них белая");
+            assert_eq!(red(&horizontal, 6, y), 51);
+            assert_eq!(red(&horizontal, 5, y), 0);
+        }
+
+        let vertical = motion(&src, 90.0, 4.0, EdgeMode::Shrink);
+        assert_eq!(vertical, src);
+    }
+
+    #[test]
+    fn test_motion_diagonal_is_symmetric() {
+        let src = image(&[(8, 4)]);
+        let dst = motion(&src, 45.0, 4.0, EdgeMode::Shrink);
+        // Против часовой стрелки при оси y вниз: точка размазывается
+        // вправо-вверх и влево-вниз, но не по другой диагонали
+        assert!(red(&dst, 9, 3) > 0);
+        assert!(red(&dst, 9, 3).abs_diff(red(&dst, 7, 5)) <= 1);
+        assert_eq!(red(&dst, 9, 5), 0);
+        assert_eq!(red(&dst, 7, 3), 0);
+    }
+
+    #[test]
+    fn test_zero_length_and_strength_are_identity() {
+        let src = image(&[(3, 3), (8, 4), (16, 0)]);
+        for edge in [EdgeMode::Shrink, EdgeMode::Wrap, EdgeMode::Transparent] {
+            assert_eq!(motion(&src, 30.0, 0.0, edge), src, "{edge:?}");
+        }
+        assert_eq!(radial(&src, [0.3, 0.7], 0.0), src);
+    }
+
+    #[test]
+    fn test_motion_edge_modes() {
+        // Белый левый столбец при wrap просачивается в правый
+        let src = image(&(0..H).map(|y| (0, y)).collect::<Vec<_>>());
+        let wrapped = motion(&src, 0.0, 2.0, EdgeMode::Wrap);
+        assert_eq!(red(&wrapped, W - 1, 4), 85);
+        let shrunk = motion(&src, 0.0, 2.0, EdgeMode::Shrink);
+        assert_eq!(red(&shrunk, W - 1, 4), 0);
+        assert_eq!(red(&shrunk, 0, 4), 128, "две выборки из трёх в изображении");
+    }
+
+    #[test]
+    fn test_radial_blurs_along_rays() {
+        // Центр изображения — пиксель (8, 4); точка справа от него на том же луче
+        let src = image(&[(12, 4)]);
+        let dst = radial(&src, [0.5, 0.5], 0.5);
+
+        assert!(red(&dst, 14, 4) > 0, "дальше по лучу точка видна");
+        assert_eq!(red(&dst, 10, 4), 0, "ближе к центру — нет");
+        assert_eq!(red(&dst, 12, 2), 0, "в стороне от луча — нет");
+        assert!(red(&dst, 12, 4) < 255);
+        // Центр размытия не меняется
+        assert_eq!(&dst[(4 * W + 8) * CHANNELS..][..4], [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_validation() {
+        let src = image(&[]);
+        let mut dst = vec![0; src.len()];
+        let edge = EdgeMode::Shrink;
+        let checkpoint = &|_| Ok(());
+        for length in [-1.0, f32::NAN, f32::INFINITY] {
+            let error = motion_blur(&src, &mut dst, W, H, 0.0, length, edge, 1, checkpoint);
+            assert_eq!(error.unwrap_err().code(), plugin_sdk::ERROR_INVALID_PARAMS);
+        }
+        for (center, strength) in [([0.5, 0.5], 1.5), ([f32::NAN, 0.5], 0.5), ([0.5, 2.0], 0.5)] {
+            let error = radial_blur(&src, &mut dst, W, H, center, strength, edge, 1, checkpoint);
+            assert_eq!(error.unwrap_err().code(), plugin_sdk::ERROR_INVALID_PARAMS);
+        }
+    }
+
+    #[test]
+    fn test_threads_match_single_thread() {
+        let src: Vec<u8> = (0..W * H * CHANNELS)
+            .map(|i| (i * 97 % 251) as u8)
+            .collect();
+        let checkpoint = &|_| Ok(());
+        for edge in [EdgeMode::Shrink, EdgeMode::Mirror, EdgeMode::Wrap] {
+            let run = |threads| {
+                let mut motion = vec![0; src.len()];
+                motion_blur(
+                    &src,
+                    &mut motion,
+                    W,
+                    H,
+                    30.0,
+                    5.0,
+                    edge,
+                    threads,
+                    checkpoint,
+                )
+                .unwrap();
+                let mut radial = vec![0; src.len()];
+                let center = [0.3, 0.6];
+                radial_blur(
+                    &src,
+                    &mut radial,
+                    W,
+                    H,
+                    center,
+                    0.4,
+                    edge,
+                    threads,
+                    checkpoint,
+                )
+                .unwrap();
+                (motion, radial)
+            };
+            let single = run(1);
+            for threads in [2, 3, 16] {
+                assert_eq!(run(threads), single, "{edge:?}, {threads} потоков");
+            }
+        }
+    }
+}
@@ -0,0 +1,321 @@
+//! Tilt-shift: размытие с радиусом, меняющимся по изображению
+//!
+//! Резкая зона — полоса (`linear`) или круг (`radial`) вокруг `center`. Вне
+//! неё радиус размытия растёт линейно на протяжении `falloff` и дальше
+//! остаётся равным `max_radius`. Ширина зоны и спада задаются в долях
+//! меньшей стороны изображения, поэтому не зависят от разрешения.
+//!
+//! Вместо отдельного окна на каждый пиксель строятся до [`MAX_LEVELS`]
+//! уровней [`box_blur`] с радиусами от 0 до `max_radius`, и каждый пиксель
+//! смешивает два уровня, между которыми лежит его радиус. Пиксели резкой зоны
+//! копируются из исходника без изменений.
+
+use crate::box_blur::box_blur;
+use crate::channel::Channel;
+use crate::edge::EdgeMode;
+use crate::parallel::Checkpoint;
+use plugin_sdk::{CHANNELS, PluginError, Result};
+use serde::Deserialize;
+
+/// Наибольшее число уровней размытия между резкой зоной и `max_radius`.
+pub const MAX_LEVELS: usize = 8;
+
+/// Форма резкой зоны.
+#[derive(Deserialize, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FocusShape {
+    /// Полоса через `center`, повёрнутая на `angle` градусов.
+    #[default]
+    Linear,
+    /// Круг с центром `center`.
+    Radial,
+}
+
+/// Геометрия резкой зоны.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Focus {
+    /// Форма зоны.
+    pub shape: FocusShape,
+    /// Центр зоны `[x, y]` в долях ширины и высоты.
+    pub center: [f32; 2],
+    /// Наклон полосы в градусах против часовой стрелки от горизонтали.
+    pub angle: f32,
+    /// Ширина полосы или диаметр круга в долях меньшей стороны.
+    pub width: f32,
+    /// Расстояние, на котором радиус дорастает до наибольшего, в долях
+    /// меньшей стороны.
+    pub falloff: f32,
+}
+
+impl Focus {
+    /// Проверяет параметры зоны.
+    pub fn check(&self) -> Result<()> {
+        if !self.center.iter().all(|c| (0.0..=1.0).contains(c)) {
+            return Err(PluginError::invalid_params(format!(
+                "focus_center задаётся в долях изображения от 0 до 1, получено {:?}",
+                self.center
+            )));
+        }
+        for (name, value) in [("focus_width", self.width), ("falloff", self.falloff)] {
+            if !(value >= 0.0 && value.is_finite()) {
+                return Err(PluginError::invalid_params(format!(
+                    "{name} должна быть неотрицательной, получено {value}"
+                )));
+            }
+        }
+        if !self.angle.is_finite() {
+            return Err(PluginError::invalid_params(format!(
+                "focus_angle должен быть конечным, получено {}",
+                self.angle
+            )));
+        }
+        Ok(())
+    }
+
+    /// Сила размытия от 0 (резко) до 1 (`max_radius`) в пикселе `(x, y)`
+    /// изображения `width × height`.
+    pub fn strength(&self, x: usize, y: usize, width: usize, height: usize) -> f32 {
+        let scale = width.min(height) as f32;
+        let dx = x as f32 - (self.center[0] * width as f32 - 0.5);
+        let dy = y as f32 - (self.center[1] * height as f32 - 0.5);
+        let distance = match self.shape {
+            FocusShape::Linear => {
+                // Расстояние до прямой вдоль (cos, -sin) — по нормали (sin, cos)
+                let (sin, cos) = self.angle.to_radians().sin_cos();
+                (dx * sin + dy * cos).abs()
+            }
+            FocusShape::Radial => dx.hypot(dy),
+        };
+        let outside = distance - self.width * scale / 2.0;
+        let falloff = self.falloff * scale;
+        if outside <= 0.0 {
+            0.0
+        } else if outside >= falloff {
+            1.0
+        } else {
+            outside / falloff
+        }
+    }
+}
+
+/// Один проход tilt-shift `src` в `dst` (оба — RGBA `width × height` с
+/// каналами `T`): радиус размытия каждого пикселя — `max_radius`, умноженный
+/// на [`Focus::strength`].
+#[allow(clippy::too_many_arguments)]
+pub fn tilt_shift_blur<T: Channel>(
+    src: &[T],
+    dst: &mut [T],
+    width: usize,
+    height: usize,
+    focus: &Focus,
+    max_radius: usize,
+    edge: EdgeMode,
+    threads: usize,
+    checkpoint: Checkpoint<'_>,
+) -> Result<()> {
+    focus.check()?;
+    dst.copy_from_slice(src);
+    let targets: Vec<f32> = (0..width * height)
+        .map(|i| focus.strength(i % width, i / width, width, height) * max_radius as f32)
+        .collect();
+    let deepest = targets.iter().copied().fold(0.0, f32::max);
+
+    let levels = max_radius.min(MAX_LEVELS);
+    let mut previous = src.to_vec();
+    let mut current = vec![T::default(); src.len()];
+    let mut low = 0;
+    for level in 1..=levels {
+        if low as f32 >= deepest {
+            break;
+        }
+        let radius = (max_radius * level).div_ceil(levels);
+        let level_checkpoint =
+            |fraction| checkpoint((level as f32 - 1.0 + fraction) / levels as f32);
+        box_blur(
+            src,
+            &mut current,
+            width,
+            height,
+            radius,
+            edge,
+            threads,
+            &level_checkpoint,
+        )?;
+
+        // Пиксели с радиусом из (low, radius] смешивают соседние уровни
+        let span = (radius - low) as f32;
+        let pixels = dst.chunks_exact_mut(CHANNELS).enumerate();
+        for ((i, pixel), &target) in pixels.zip(&targets) {
+            if target <= low as f32 || target > radius as f32 {
+                continue;
+            }
+            let weight = (target - low as f32) / span;
+            let range = i * CHANNELS..(i + 1) * CHANNELS;
+            let levels = previous[range.clone()].iter().zip(&current[range]);
+            for (out, (&from, &to)) in pixel.iter_mut().zip(levels) {
+                let (from, to) = (from.to_f32(), to.to_f32());
+                *out = T::from_f32(from + (to - from) * weight);
+            }
+        }
+        std::mem::swap(&mut previous, &mut current);
+        low = radius;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: usize = 16;
+    const H: usize = 24;
+
+    fn band() -> Focus {
+        Focus {
+            shape: FocusShape::Linear,
+            center: [0.5, 0.5],
+            angle: 0.0,
+            width: 0.25,
+            falloff: 0.5,
+        }
+    }
+
+    fn source() -> Vec<u8> {
+        (0..W * H * CHANNELS)
+            .map(|i| (i * 131 % 253) as u8)
+            .collect()
+    }
+
+    fn run(focus: &Focus, max_radius: usize) -> Vec<u8> {
+        run_threads(focus, max_radius, 1)
+    }
+
+    fn run_threads(focus: &Focus, max_radius: usize, threads: usize) -> Vec<u8> {
+        let src = source();
+        let mut dst = vec![0; src.len()];
+        let edge = EdgeMode::Clamp;
+        tilt_shift_blur(
+            &src,
+            &mut dst,
+            W,
+            H,
+            focus,
+            max_radius,
+            edge,
+            threads,
+            &|_| Ok(()),
+        )
+        .unwrap();
+        dst
+    }
+
+    fn row(data: &[u8], y: usize) -> &[u8] {
+        &data[y * W * CHANNELS..(y + 1) * W * CHANNELS]
+    }
+
+    #[test]
+    fn test_strength_profile() {
+        // Меньшая сторона 16: полоса ±2 пикселя от центра 11.5, спад 8 пикселей
+        let focus = band();
+        let strength = |y| focus.strength(3, y, W, H);
+        assert_eq!([strength(10), strength(11), strength(13)], [0.0; 3]);
+        assert_eq!(strength(17), 0.4375);
+        assert_eq!([strength(0), strength(23)], [1.0, 1.0]);
+
+        // Вертикальная полоса: расстояние меряется по горизонтали
+        let vertical = Focus {
+            angle: 90.0,
+            ..focus
+        };
+        assert_eq!(vertical.strength(8, 0, W, H), 0.0);
+        assert!((vertical.strength(0, 12, W, H) - 0.6875).abs() < 1e-5);
+
+        // Круг: в центре резко, в углу размыто полностью
+        let circle = Focus {
+            shape: FocusShape::Radial,
+            ..focus
+        };
+        assert_eq!(circle.strength(8, 12, W, H), 0.0);
+        assert_eq!(circle.strength(0, 0, W, H), 1.0);
+    }
+
+    #[test]
+    fn test_focus_band_sharp_far_rows_fully_blurred() {
+        let src = source();
+        let dst = run(&band(), 3);
+        for y in 10..=13 {
+            assert_eq!(row(&dst, y), row(&src, y), "строка {y} в резкой зоне");
+        }
+
+        let mut full = vec![0; src.len()];
+        box_blur(&src, &mut full, W, H, 3, EdgeMode::Clamp, 1, &|_| Ok(())).unwrap();
+        for y in [0, 1, 22, 23] {
+            assert_eq!(row(&dst, y), row(&full, y), "строка {y} за спадом");
+        }
+        // В спаде — смесь: не исходник и не полное размытие
+        assert_ne!(row(&dst, 17), row(&src, 17));
+        assert_ne!(row(&dst, 17), row(&full, 17));
+    }
+
+    #[test]
+    fn test_levels_for_large_radius() {
+        // Радиус больше MAX_LEVELS: уровни неравномерны, но края всё равно
+        // получают ровно max_radius
+        let src = source();
+        let dst = run(&band(), 20);
+        let mut full = vec![0; src.len()];
+        box_blur(&src, &mut full, W, H, 20, EdgeMode::Clamp, 1, &|_| Ok(())).unwrap();
+        assert_eq!(row(&dst, 0), row(&full, 0));
+        assert_eq!(row(&dst, 12), row(&src, 12));
+    }
+
+    #[test]
+    fn test_zero_radius_identity() {
+        assert_eq!(run(&band(), 0), source());
+    }
+
+    #[test]
+    fn test_validation() {
+        let invalid = [
+            Focus {
+                center: [0.5, 1.5],
+                ..band()
+            },
+            Focus {
+                width: -0.1,
+                ..band()
+            },
+            Focus {
+                falloff: f32::NAN,
+                ..band()
+            },
+            Focus {
+                angle: f32::INFINITY,
+                ..band()
+            },
+        ];
+        for focus in invalid {
+            let error = focus.check().unwrap_err();
+            assert_eq!(error.code(), plugin_sdk::ERROR_INVALID_PARAMS, "{focus:?}");
+        }
+    }
+
+    #[test]
+    fn test_threads_match_single_thread() {
+        let radial = Focus {
+            shape: FocusShape::Radial,
+            center: [0.3, 0.6],
+            ..band()
+        };
+        for focus in [band(), radial] {
+            let single = run_threads(&focus, 5, 1);
+            for threads in [2, 3, 16] {
+                assert_eq!(
+                    run_threads(&focus, 5, threads),
+                    single,
+                    "{focus:?}, {threads} потоков"
+                );
+            }
+        }
+    }
+}
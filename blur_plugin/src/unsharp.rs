@@ -0,0 +1,209 @@
+//! Нерезкое маскирование (unsharp mask)
+//!
+//! Повышение резкости, обратное размытию: из исходника вычитается его
+//! гауссово размытие ([`gaussian_blur`]), и разница, умноженная на `amount`,
+//! прибавляется обратно — `исходник + amount * (исходник - размытое)`. По
+//! обе стороны границы контраст растёт, а ровные участки не меняются.
+//! Разницы меньше `threshold` (в долях полной шкалы канала) не усиливаются,
+//! чтобы не подчёркивать слабый шум. Для премультиплицированного цвета
+//! полная шкала пикселя — его альфа, поэтому порог сравнивается с разницей
+//! исходного цвета и одинаков для прозрачных и непрозрачных пикселей.
+//! Альфа-канал не меняется.
+
+use crate::channel::Channel;
+use crate::edge::EdgeMode;
+use crate::gaussian::gaussian_blur;
+use crate::parallel::Checkpoint;
+use plugin_sdk::{CHANNELS, PluginError, Result};
+
+/// Проверяет силу и порог.
+pub fn check_unsharp(amount: f32, threshold: f32) -> Result<()> {
+    if !(amount >= 0.0 && amount.is_finite()) {
+        return Err(PluginError::invalid_params(format!(
+            "amount должна быть неотрицательной, получено {amount}"
+        )));
+    }
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(PluginError::invalid_params(format!(
+            "threshold должен быть от 0 до 1, получено {threshold}"
+        )));
+    }
+    Ok(())
+}
+
+/// Один проход нерезкого маскирования `src` в `dst` (оба — RGBA
+/// `width × height` с каналами `T`) с гауссовым ядром `sigma`;
+/// `premultiplied` — цвет в буферах умножен на альфу.
+#[allow(clippy::too_many_arguments)]
+pub fn unsharp_mask<T: Channel>(
+    src: &[T],
+    dst: &mut [T],
+    width: usize,
+    height: usize,
+    sigma: f32,
+    amount: f32,
+    threshold: f32,
+    premultiplied: bool,
+    edge: EdgeMode,
+    threads: usize,
+    checkpoint: Checkpoint<'_>,
+) -> Result<()> {
+    check_unsharp(amount, threshold)?;
+    gaussian_blur(src, dst, width, height, sigma, edge, threads, checkpoint)?;
+
+    for (pixel, source) in dst
+        .chunks_exact_mut(CHANNELS)
+        .zip(src.chunks_exact(CHANNELS))
+    {
+        // Премультиплицированный цвет пикселя лежит в пределах его альфы
+        let full = if premultiplied {
+            source[3].to_f32()
+        } else {
+            T::MAX as f32
+        };
+        let limit = threshold * full;
+        for (out, &original) in pixel[..3].iter_mut().zip(&source[..3]) {
+            let detail = original.to_f32() - out.to_f32();
+            *out = if detail.abs() < limit {
+                original
+            } else {
+                T::from_f32(original.to_f32() + amount * detail)
+            };
+        }
+        pixel[3] = source[3];
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: usize = 16;
+
+    /// Строка `W × 1` из серых значений
+    fn gray_row(values: impl Fn(usize) -> u8) -> Vec<u8> {
+        (0..W)
+            .flat_map(|x| {
+                let value = values(x);
+                [value, value, value, 255]
+            })
+            .collect()
+    }
+
+    fn sharpen(src: &[u8], amount: f32, threshold: f32) -> Vec<u8> {
+        let mut dst = vec![0; src.len()];
+        let edge = EdgeMode::Clamp;
+        let checkpoint = &|_| Ok(());
+        unsharp_mask(
+            src, &mut dst, W, 1, 1.5, amount, threshold, false, edge, 1, checkpoint,
+        )
+        .unwrap();
+        dst
+    }
+
+    fn red(data: &[u8]) -> Vec<u8> {
+        data.chunks_exact(CHANNELS).map(|p| p[0]).collect()
+    }
+
+    #[test]
+    fn test_edge_gains_contrast() {
+        let src = gray_row(|x| if x < W / 2 { 60 } else { 180 });
+        let dst = red(&sharpen(&src, 1.0, 0.0));
+
+        // У границы тёмная сторона темнеет, светлая светлеет
+        assert!(dst[W / 2 - 1] < 60, "{dst:?}");
+        assert!(dst[W / 2] > 180, "{dst:?}");
+        assert!(dst[W / 2] - dst[W / 2 - 1] > 120, "{dst:?}");
+        // Вдали от границы ничего не меняется
+        assert_eq!([dst[0], dst[W - 1]], [60, 180]);
+        assert!(sharpen(&src, 1.0, 0.0).chunks_exact(4).all(|p| p[3] == 255));
+    }
+
+    #[test]
+    fn test_threshold_keeps_noise() {
+        // Шум ±4 на ровном сером меньше порога 0.05 (≈ 13 уровней)
+        let noisy = gray_row(|x| [124, 128, 132, 126, 130][x % 5]);
+        assert_eq!(sharpen(&noisy, 2.0, 0.05), noisy);
+        assert_ne!(sharpen(&noisy, 2.0, 0.0), noisy);
+
+        // Сильная граница порог проходит
+        let step = gray_row(|x| if x < W / 2 { 60 } else { 180 });
+        assert_ne!(sharpen(&step, 1.0, 0.05), step);
+    }
+
+    #[test]
+    fn test_threshold_independent_of_alpha() {
+        // Одна и та же граница и один и тот же шум, непрозрачные и
+        // полупрозрачные, в премультиплицированном виде
+        let with_alpha = |row: Vec<u8>, alpha: u8| -> Vec<u16> {
+            let mut row = row;
+            row.chunks_exact_mut(CHANNELS).for_each(|p| p[3] = alpha);
+            crate::alpha::premultiply(&row)
+        };
+        let step = || gray_row(|x| if x < W / 2 { 60 } else { 180 });
+        let noise = || gray_row(|x| [124, 128, 132, 126, 130][x % 5]);
+        let sharpen = |src: &[u16]| {
+            let mut dst = vec![0; src.len()];
+            let edge = EdgeMode::Clamp;
+            unsharp_mask(src, &mut dst, W, 1, 1.5, 1.0, 0.05, true, edge, 1, &|_| {
+                Ok(())
+            })
+            .unwrap();
+            dst
+        };
+
+        for alpha in [255, 64] {
+            let edge = with_alpha(step(), alpha);
+            assert_ne!(sharpen(&edge), edge, "граница при альфе {alpha}");
+            let flat = with_alpha(noise(), alpha);
+            assert_eq!(sharpen(&flat), flat, "шум при альфе {alpha}");
+        }
+    }
+
+    #[test]
+    fn test_zero_amount_identity() {
+        let src = gray_row(|x| (x * 37 % 256) as u8);
+        assert_eq!(sharpen(&src, 0.0, 0.0), src);
+    }
+
+    #[test]
+    fn test_validation() {
+        for (amount, threshold) in [(-1.0, 0.0), (f32::NAN, 0.0), (1.0, 1.5), (1.0, -0.1)] {
+            let error = check_unsharp(amount, threshold).unwrap_err();
+            assert_eq!(error.code(), plugin_sdk::ERROR_INVALID_PARAMS);
+        }
+        assert!(check_unsharp(3.0, 1.0).is_ok());
+    }
+
+    #[test]
+    fn test_threads_match_single_thread() {
+        let (width, height) = (11, 9);
+        let src: Vec<u16> = (0..width * height * CHANNELS)
+            .map(|i| (i * 9973 % 65521) as u16)
+            .collect();
+        let edge = EdgeMode::Mirror;
+        let run = |threads| {
+            let mut dst = vec![0; src.len()];
+            unsharp_mask(
+                &src,
+                &mut dst,
+                width,
+                height,
+                1.5,
+                2.0,
+                0.02,
+                true,
+                edge,
+                threads,
+                &|_| Ok(()),
+            )
+            .unwrap();
+            dst
+        };
+        let single = run(1);
+        for threads in [2, 3, 16] {
+            assert_eq!(run(threads), single, "{threads} потоков");
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use image::ImageFormat;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::error::AppError;
+use crate::output;
+use crate::pipeline::Pipeline;
+use crate::progress;
+
+/// Default output name template: keep the input file name
+pub const DEFAULT_NAME_TEMPLATE: &str = "{name}";
+
+/// Outcome of a single file of the batch
+pub struct BatchEntry {
+    pub input: PathBuf,
+    pub result: Result<PathBuf, AppError>,
+}
+
+/// Results of a whole batch, in input order
+pub struct BatchReport {
+    pub entries: Vec<BatchEntry>,
+}
+
+impl BatchReport {
+    pub fn failed(&self) -> usize {
+        self.entries.iter().filter(|e| e.result.is_err()).count()
+    }
+
+    /// Prints a short summary and every failure to stderr
+    pub fn print_summary(&self) {
+        let total = self.entries.len();
+        let failed = self.failed();
+        eprintln!("Processed {} of {total} files", total - failed);
+        for entry in &self.entries {
+            if let Err(err) = &entry.result {
+                eprintln!("  {}: {err}", entry.input.display());
+            }
+        }
+    }
+}
+
+/// Collects input images from a directory or a glob pattern
+///
+/// Directory entries are filtered by a known image extension, glob matches
+/// are taken as is. The result is sorted so batches run deterministically.
+pub fn collect_inputs(input: &str) -> Result<Vec<PathBuf>, AppError> {
+    let path = Path::new(input);
+    let mut files = if path.is_dir() {
+        let mut files = Vec::new();
+        for entry in path.read_dir()? {
+            let file = entry?.path();
+            if file.is_file() && ImageFormat::from_path(&file).is_ok() {
+                files.push(file);
+            }
+        }
+        files
+    } else {
+        let pattern = glob::glob(input).map_err(|e| AppError::InvalidGlob {
+            pattern: input.to_owned(),
+            reason: e.to_string(),
+        })?;
+        pattern
+            .filter_map(Result::ok)
+            .filter(|file| file.is_file())
+            .collect()
+    };
+
+    if files.is_empty() {
+        return Err(AppError::NoInputFiles(input.to_owned()));
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Expands `{name}`, `{stem}`, `{ext}` and `{index}` in the output template
+pub fn output_name(template: &str, input: &Path, index: usize) -> String {
+    let name = input.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    let stem = input.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let ext = input.extension().and_then(|s| s.to_str()).unwrap_or("");
+    template
+        .replace("{name}", name)
+        .replace("{stem}", stem)
+        .replace("{ext}", ext)
+        .replace("{index}", &index.to_string())
+}
+
+/// Output path of every input, in input order
+///
+/// Fails before anything is written when two inputs expand to the same
+/// output, or when an output is one of the inputs, e.g. `{name}` with
+/// `output_dir` being the input directory.
+pub fn output_paths(
+    inputs: &[PathBuf],
+    output_dir: &Path,
+    template: &str,
+) -> Result<Vec<PathBuf>, AppError> {
+    let outputs: Vec<PathBuf> = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| output_dir.join(output_name(template, input, index)))
+        .collect();
+
+    let mut first: HashMap<&Path, usize> = HashMap::new();
+    for (index, output) in outputs.iter().enumerate() {
+        if first.insert(output, index).is_some() {
+            return Err(AppError::OutputCollision {
+                output: output.clone(),
+                inputs: inputs
+                    .iter()
+                    .zip(&outputs)
+                    .filter(|(_, other)| *other == output)
+                    .map(|(input, _)| input.clone())
+                    .collect(),
+            });
+        }
+    }
+
+    // Outputs do not exist yet, so they are compared through the canonical
+    // output directory; without the directory there is nothing to overwrite
+    let Ok(dir) = output_dir.canonicalize() else {
+        return Ok(outputs);
+    };
+    let canonical_inputs: HashMap<PathBuf, &PathBuf> = inputs
+        .iter()
+        .filter_map(|input| Some((input.canonicalize().ok()?, input)))
+        .collect();
+    for (index, (output, input)) in outputs.iter().zip(inputs).enumerate() {
+        let canonical = dir.join(output_name(template, input, index));
+        if let Some(&overwritten) = canonical_inputs.get(&canonical) {
+            return Err(AppError::OutputOverwritesInput {
+                output: output.clone(),
+                input: overwritten.clone(),
+            });
+        }
+    }
+    Ok(outputs)
+}
+
+fn process_file(pipeline: &Pipeline, input: &Path, output: &Path) -> Result<(), AppError> {
+    let mut img = image::open(input)?.to_rgba8();
+    pipeline.run(&mut img)?;
+    output::save(img, output, ImageFormat::from_path(output)?)
+}
+
+/// Runs the pipeline over every input on `jobs` worker threads
+///
+/// A failing file is recorded in the report and does not stop the others.
+/// Once cancellation is requested no new files are started, and files that
+/// were never started are left out of the report.
+pub fn run(
+    pipeline: &Pipeline,
+    inputs: Vec<PathBuf>,
+    output_dir: &Path,
+    template: &str,
+    jobs: usize,
+) -> Result<BatchReport, AppError> {
+    let outputs = output_paths(&inputs, output_dir, template)?;
+    std::fs::create_dir_all(output_dir)?;
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Result<PathBuf, AppError>>>> =
+        Mutex::new(inputs.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, inputs.len().max(1)) {
+            scope.spawn(|| {
+                while !progress::cancel_requested() {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(input) = inputs.get(index) else {
+                        break;
+                    };
+                    let output = &outputs[index];
+                    let result = process_file(pipeline, input, output).map(|()| output.clone());
+                    results.lock().unwrap()[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    let entries = inputs
+        .into_iter()
+        .zip(results.into_inner().unwrap())
+        .filter_map(|(input, result)| {
+            Some(BatchEntry {
+                input,
+                result: result?,
+            })
+        })
+        .collect();
+
+    Ok(BatchReport { entries })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_name() {
+        let input = Path::new("photos/cat.png");
+        assert_eq!(output_name("{name}", input, 0), "cat.png");
+        assert_eq!(output_name("{stem}_blur.{ext}", input, 0), "cat_blur.png");
+        assert_eq!(output_name("{index}_{stem}.jpg", input, 7), "7_cat.jpg");
+    }
+
+    #[test]
+    fn test_duplicate_outputs_rejected() {
+        let inputs = [PathBuf::from("in/a.png"), PathBuf::from("in/a.jpg")];
+        let err = output_paths(&inputs, Path::new("out"), "{stem}.png").unwrap_err();
+        assert!(
+            matches!(&err, AppError::OutputCollision { output, inputs: sources }
+                if output == Path::new("out/a.png") && sources == &inputs),
+            "{err}"
+        );
+        assert_eq!(
+            err.to_string(),
+            "Inputs in/a.png, in/a.jpg would all be written to out/a.png"
+        );
+
+        let outputs = output_paths(&inputs, Path::new("out"), "{name}").unwrap();
+        assert_eq!(outputs, [Path::new("out/a.png"), Path::new("out/a.jpg")]);
+    }
+
+    #[test]
+    fn test_output_overwriting_input_rejected() {
+        let dir =
+            std::env::temp_dir().join(format!("image_processor_batch_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("cat.png");
+        std::fs::write(&input, b"").unwrap();
+
+        // Same directory spelled differently still clashes
+        let spelled = dir.join(".");
+        let inputs = [input.clone()];
+        let clash = output_paths(&inputs, &spelled, "{name}");
+        let renamed = output_paths(&inputs, &spelled, "{stem}_blur.{ext}");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(
+            matches!(&clash, Err(AppError::OutputOverwritesInput { input: overwritten, .. })
+                if *overwritten == input),
+            "{:?}",
+            clash.map_err(|e| e.to_string())
+        );
+        assert_eq!(renamed.unwrap(), [spelled.join("cat_blur.png")]);
+    }
+}
@@ -0,0 +1,154 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::error::AppError;
+use crate::plugin_loader::Plugin;
+
+/// Environment variable with extra plugin directories, in `PATH` syntax
+pub const PLUGIN_PATH_ENV: &str = "IMAGE_PROCESSOR_PLUGIN_PATH";
+
+/// Directory searched when neither `--plugin-path` nor the env var is set
+pub const DEFAULT_PLUGIN_PATH: &str = "target/debug";
+
+fn platform_library_name(name: &str) -> String {
+    if cfg!(target_os = "windows") {
+        format!("{name}.dll")
+    } else if cfg!(target_os = "macos") {
+        format!("lib{name}.dylib")
+    } else {
+        format!("lib{name}.so")
+    }
+}
+
+/// Returns true if the file name looks like a dynamic library on this platform
+fn is_platform_library(path: &Path) -> bool {
+    let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    if cfg!(target_os = "windows") {
+        file_name.ends_with(".dll")
+    } else if cfg!(target_os = "macos") {
+        file_name.starts_with("lib") && file_name.ends_with(".dylib")
+    } else {
+        file_name.starts_with("lib") && file_name.ends_with(".so")
+    }
+}
+
+/// Ordered list of directories plugins are looked up in
+#[derive(Debug, Clone)]
+pub struct PluginSearchPath {
+    dirs: Vec<PathBuf>,
+}
+
+impl PluginSearchPath {
+    /// Builds the search path: CLI directories first, then the env var
+    ///
+    /// Falls back to [`DEFAULT_PLUGIN_PATH`] when both are empty.
+    pub fn new(cli_dirs: Vec<PathBuf>) -> Self {
+        let env_dirs = env::var_os(PLUGIN_PATH_ENV)
+            .map(|value| env::split_paths(&value).collect::<Vec<_>>())
+            .unwrap_or_default();
+        Self::from_dirs(cli_dirs.into_iter().chain(env_dirs).collect())
+    }
+
+    fn from_dirs(dirs: Vec<PathBuf>) -> Self {
+        let mut dirs: Vec<PathBuf> = dirs
+            .into_iter()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .collect();
+        if dirs.is_empty() {
+            dirs.push(PathBuf::from(DEFAULT_PLUGIN_PATH));
+        }
+        Self { dirs }
+    }
+
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// Returns the library path of `name` in the first directory that has it
+    pub fn find(&self, name: &str) -> Result<PathBuf, AppError> {
+        let lib_name = platform_library_name(name);
+        self.dirs
+            .iter()
+            .map(|dir| dir.join(&lib_name))
+            .find(|path| path.is_file())
+            .ok_or_else(|| AppError::PluginNotFound {
+                name: name.to_owned(),
+                searched: self.dirs.clone(),
+            })
+    }
+
+    /// Finds and loads the plugin called `name`
+    pub fn load(&self, name: &str) -> Result<Plugin, AppError> {
+        Plugin::load(&self.find(name)?)
+    }
+
+    /// Tries to load every library in the search path
+    ///
+    /// Libraries that fail to load are returned with their error so the
+    /// caller can report them. A plugin name found in several directories is
+    /// only taken from the first one, matching [`PluginSearchPath::find`].
+    pub fn discover(&self) -> Vec<Result<Plugin, (PathBuf, AppError)>> {
+        let mut seen = Vec::new();
+        let mut found = Vec::new();
+
+        for dir in &self.dirs {
+            let Ok(entries) = dir.read_dir() else {
+                continue;
+            };
+            let mut libs: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file() && is_platform_library(path))
+                .collect();
+            libs.sort();
+
+            for lib in libs {
+                let file_name = lib.file_name().map(|name| name.to_owned());
+                if seen.contains(&file_name) {
+                    continue;
+                }
+                seen.push(file_name);
+                found.push(Plugin::load(&lib).map_err(|err| (lib, err)));
+            }
+        }
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_search_path() {
+        let search = PluginSearchPath::from_dirs(Vec::new());
+        assert_eq!(search.dirs(), [PathBuf::from(DEFAULT_PLUGIN_PATH)]);
+    }
+
+    #[test]
+    fn test_search_path_keeps_order() {
+        let search = PluginSearchPath::from_dirs(vec![
+            PathBuf::from("plugins"),
+            PathBuf::new(),
+            PathBuf::from("/usr/lib/image_processor"),
+        ]);
+        assert_eq!(
+            search.dirs(),
+            [
+                PathBuf::from("plugins"),
+                PathBuf::from("/usr/lib/image_processor")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plugin_not_found() {
+        let search = PluginSearchPath::from_dirs(vec![PathBuf::from("no/such/dir")]);
+        assert!(matches!(
+            search.find("blur_plugin"),
+            Err(AppError::PluginNotFound { .. })
+        ));
+    }
+}
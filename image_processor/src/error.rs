@@ -1,6 +1,23 @@
 use std::path::PathBuf;
+use std::time::Duration;
 use thiserror::Error;
 
+fn join_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn or_no_details(message: &str) -> &str {
+    if message.is_empty() {
+        "no details"
+    } else {
+        message
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum AppError {
     /// Error when the input image file is not found
@@ -11,9 +28,12 @@ pub enum AppError {
     #[error("Params file not found: {0}")]
     ParamsFileNotFound(PathBuf),
 
-    /// Error when the plugin library cannot be found or loaded
-    #[error("Plugin not found: {0}")]
-    PluginNotFound(PathBuf),
+    /// Error when the plugin library is not present in any search directory
+    #[error("Plugin `{name}` not found in: {}", join_paths(.searched))]
+    PluginNotFound {
+        name: String,
+        searched: Vec<PathBuf>,
+    },
 
     /// Error when the plugin does not contain the required `process_image` function
     #[error("Failed to load image: {0}")]
@@ -30,4 +50,113 @@ pub enum AppError {
     /// Error when the plugin library cannot be loaded
     #[error("Invalid UTF-8 in params")]
     InvalidUtf8(#[from] std::str::Utf8Error),
+
+    /// Error when the plugin does not export a `plugin_descriptor` table
+    #[error("Plugin {0} does not export `plugin_descriptor`, refusing to load it")]
+    MissingDescriptor(PathBuf),
+
+    /// Error when the plugin was built against a different ABI version
+    #[error("Plugin {path} uses ABI version {found}, expected {expected}")]
+    IncompatibleAbi {
+        path: PathBuf,
+        expected: u32,
+        found: u32,
+    },
+
+    /// Error when the plugin descriptor contains malformed data
+    #[error("Plugin {path} has an invalid descriptor: {reason}")]
+    InvalidDescriptor { path: PathBuf, reason: String },
+
+    /// Error when the plugin does not support the RGBA8 pixel format
+    #[error("Plugin `{plugin}` does not support the RGBA8 pixel format")]
+    UnsupportedPixelFormat { plugin: String },
+
+    /// Error when the parameters file contains an interior NUL byte
+    #[error("Params file contains a NUL byte: {0}")]
+    ParamsContainNul(PathBuf),
+
+    /// Error when no processing steps were given
+    #[error("No processing steps given: pass PLUGIN PARAMS or at least one --step")]
+    EmptyPipeline,
+
+    /// Error when the pipeline description file is not found
+    #[error("Pipeline file not found: {0}")]
+    PipelineFileNotFound(PathBuf),
+
+    /// Error when the pipeline description file cannot be parsed
+    #[error("Invalid pipeline file {path}: {reason}")]
+    InvalidPipelineFile { path: PathBuf, reason: String },
+
+    /// Error when the requested output format is not known
+    #[error("Unsupported output format: {0}")]
+    UnsupportedOutputFormat(String),
+
+    /// Error when the batch input glob pattern is malformed
+    #[error("Invalid glob pattern `{pattern}`: {reason}")]
+    InvalidGlob { pattern: String, reason: String },
+
+    /// Error when the batch input matches no image files
+    #[error("No input images found for `{0}`")]
+    NoInputFiles(String),
+
+    /// Error when some files of a batch could not be processed
+    #[error("{failed} of {total} files failed to process")]
+    BatchFailed { failed: usize, total: usize },
+
+    /// Error when several batch inputs expand to the same output file
+    #[error("Inputs {} would all be written to {output}", join_paths(.inputs))]
+    OutputCollision {
+        output: PathBuf,
+        inputs: Vec<PathBuf>,
+    },
+
+    /// Error when a batch output would replace one of the inputs
+    #[error("Output {output} would overwrite input {input}")]
+    OutputOverwritesInput { output: PathBuf, input: PathBuf },
+
+    /// Error when params do not match the schema exported by the plugin
+    #[error("Invalid params for plugin `{plugin}` ({origin}): {}", errors.join("; "))]
+    InvalidParams {
+        plugin: String,
+        origin: String,
+        errors: Vec<String>,
+    },
+
+    /// Error when a plugin panicked and caught the panic at its FFI boundary
+    #[error("Plugin `{plugin}` panicked: {message}")]
+    PluginPanicked { plugin: String, message: String },
+
+    /// Error when an isolated plugin worker dies before answering
+    #[error("Plugin `{plugin}` crashed in its worker process ({status})")]
+    PluginCrashed { plugin: String, status: String },
+
+    /// Error when a plugin does not finish within the allowed time
+    #[error("Plugin `{plugin}` timed out after {timeout:?}")]
+    PluginTimedOut { plugin: String, timeout: Duration },
+
+    /// Error when the plugin reports a non-zero status from `process_image`
+    ///
+    /// `message` comes from the optional `plugin_last_error` export and is
+    /// empty when the plugin gives no details.
+    #[error(
+        "Plugin `{plugin}` failed with status {code}: {}",
+        or_no_details(message)
+    )]
+    PluginFailed {
+        plugin: String,
+        code: i32,
+        message: String,
+    },
+
+    /// Error when processing was cancelled, e.g. with Ctrl-C
+    #[error("Processing cancelled")]
+    Cancelled,
+
+    /// Error when the Ctrl-C handler cannot be installed
+    #[error("Cannot install the Ctrl-C handler: {0}")]
+    InterruptHandler(#[from] ctrlc::Error),
+
+    /// Error when `transform_image` returns a buffer that does not match its size
+    #[error("Plugin `{plugin}` returned an invalid image: {reason}")]
+    InvalidPluginOutput { plugin: String, reason: String },
 }
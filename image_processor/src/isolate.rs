@@ -0,0 +1,524 @@
+//! Out-of-process plugin execution
+//!
+//! The host re-runs its own executable with the hidden `worker` subcommand.
+//! The worker loads a single plugin library, reads one request from stdin,
+//! calls `process_image` and writes the response to stdout, so a panic or a
+//! segfault inside the plugin only kills the worker.
+//!
+//! All integers are little-endian.
+//!
+//! Request: `width: u32`, `height: u32`, `params_len: u32`, params bytes,
+//! then `width * height * 4` RGBA bytes.
+//!
+//! The worker answers with a sequence of frames, each starting with a tag
+//! byte:
+//!
+//! - progress (`1`): `fraction: f32`, `stage_len: u32`, stage bytes;
+//! - result (`0`): `status: i32`, `message_len: u32`, message bytes, then,
+//!   if `status` is zero, `width: u32`, `height: u32` of the processed image
+//!   and its `width * height * 4` RGBA bytes;
+//! - invalid output (`2`): `reason_len: u32`, reason bytes, when the worker
+//!   rejected the image a transforming plugin returned.
+//!
+//! Every plugin-side error, cancellation included, ends with a result or
+//! invalid output frame, so only a worker that dies without one counts as
+//! a crash.
+//!
+//! Ctrl-C reaches the worker as well, so an isolated plugin is cancelled
+//! the same way as an in-process one; the host also kills the worker once
+//! cancellation is requested, and a worker that exits with an error after
+//! Ctrl-C is reported as cancelled rather than crashed.
+
+use std::ffi::{CStr, CString};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use image::RgbaImage;
+use plugin_sdk::abi::{ERROR_CANCELLED, ERROR_PANIC};
+
+use crate::error::AppError;
+use crate::plugin_loader::{Plugin, status_error};
+use crate::progress::{self, Progress};
+
+/// Name of the hidden subcommand that runs the worker side
+pub const WORKER_COMMAND: &str = "worker";
+
+/// How often the host checks whether the worker has exited
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Tag of the final frame carrying the plugin status
+const FRAME_RESULT: u8 = 0;
+/// Tag of a progress report frame
+const FRAME_PROGRESS: u8 = 1;
+/// Tag of the final frame rejecting a transforming plugin's output
+const FRAME_INVALID_OUTPUT: u8 = 2;
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0; 1];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_bytes(reader: &mut impl Read, len: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![0; len];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn write_request(
+    writer: impl Write,
+    width: u32,
+    height: u32,
+    params: &CStr,
+    pixels: &[u8],
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    let params = params.to_bytes();
+    writer.write_all(&width.to_le_bytes())?;
+    writer.write_all(&height.to_le_bytes())?;
+    writer.write_all(&(params.len() as u32).to_le_bytes())?;
+    writer.write_all(params)?;
+    writer.write_all(pixels)?;
+    writer.flush()
+}
+
+fn pixels_len(width: u32, height: u32) -> io::Result<usize> {
+    usize::try_from(u64::from(width) * u64::from(height) * 4)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn read_image(reader: &mut impl Read) -> io::Result<RgbaImage> {
+    let width = read_u32(reader)?;
+    let height = read_u32(reader)?;
+    let pixels = read_bytes(reader, pixels_len(width, height)?)?;
+    Ok(RgbaImage::from_raw(width, height, pixels).expect("buffer length matches dimensions"))
+}
+
+/// Final frame of the worker
+#[derive(Debug)]
+enum Response {
+    /// Plugin status and message, plus the processed image when the call
+    /// succeeded
+    Result {
+        status: i32,
+        message: String,
+        image: Option<RgbaImage>,
+    },
+    /// Reason the worker rejected the image a transforming plugin returned
+    InvalidOutput(String),
+}
+
+impl Response {
+    /// The processed image, or the error the response reports for `plugin`
+    fn into_image(self, plugin: String) -> Result<RgbaImage, AppError> {
+        match self {
+            Response::Result {
+                image: Some(image), ..
+            } => Ok(image),
+            Response::Result {
+                status, message, ..
+            } => Err(status_error(plugin, status, message)),
+            Response::InvalidOutput(reason) => {
+                Err(AppError::InvalidPluginOutput { plugin, reason })
+            }
+        }
+    }
+}
+
+fn write_progress(mut writer: impl Write, fraction: f32, stage: &str) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(9 + stage.len());
+    frame.push(FRAME_PROGRESS);
+    frame.extend_from_slice(&fraction.to_le_bytes());
+    frame.extend_from_slice(&(stage.len() as u32).to_le_bytes());
+    frame.extend_from_slice(stage.as_bytes());
+    writer.write_all(&frame)?;
+    writer.flush()
+}
+
+fn write_response(writer: impl Write, response: &Response) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    match response {
+        Response::Result {
+            status,
+            message,
+            image,
+        } => {
+            writer.write_all(&[FRAME_RESULT])?;
+            writer.write_all(&status.to_le_bytes())?;
+            writer.write_all(&(message.len() as u32).to_le_bytes())?;
+            writer.write_all(message.as_bytes())?;
+            if let Some(image) = image {
+                writer.write_all(&image.width().to_le_bytes())?;
+                writer.write_all(&image.height().to_le_bytes())?;
+                writer.write_all(image.as_raw())?;
+            }
+        }
+        Response::InvalidOutput(reason) => {
+            writer.write_all(&[FRAME_INVALID_OUTPUT])?;
+            writer.write_all(&(reason.len() as u32).to_le_bytes())?;
+            writer.write_all(reason.as_bytes())?;
+        }
+    }
+    writer.flush()
+}
+
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    let len = read_u32(reader)? as usize;
+    Ok(String::from_utf8_lossy(&read_bytes(reader, len)?).into_owned())
+}
+
+/// Reads frames up to the final one, forwarding progress frames to `progress`
+fn read_response(reader: impl Read, progress: &dyn Progress) -> io::Result<Response> {
+    let mut reader = BufReader::new(reader);
+    loop {
+        match read_u8(&mut reader)? {
+            FRAME_RESULT => break,
+            FRAME_INVALID_OUTPUT => return Ok(Response::InvalidOutput(read_string(&mut reader)?)),
+            FRAME_PROGRESS => {
+                let fraction = f32::from_bits(read_u32(&mut reader)?);
+                let stage = read_string(&mut reader)?;
+                progress.report(fraction, &stage);
+            }
+            tag => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown frame tag {tag}"),
+                ));
+            }
+        }
+    }
+    let status = read_u32(&mut reader)? as i32;
+    let message = read_string(&mut reader)?;
+    let image = if status == 0 {
+        Some(read_image(&mut reader)?)
+    } else {
+        None
+    };
+    Ok(Response::Result {
+        status,
+        message,
+        image,
+    })
+}
+
+/// How the worker ended, as seen by the host
+enum Exit {
+    Finished(ExitStatus),
+    TimedOut,
+    Cancelled,
+}
+
+/// Exit of a worker that ended on its own; a failure after Ctrl-C is the
+/// worker being interrupted, not crashing
+fn finished(status: ExitStatus, cancel_requested: bool) -> Exit {
+    if cancel_requested && !status.success() {
+        Exit::Cancelled
+    } else {
+        Exit::Finished(status)
+    }
+}
+
+fn kill(child: &mut Child, exit: Exit) -> io::Result<Exit> {
+    child.kill()?;
+    child.wait()?;
+    Ok(exit)
+}
+
+fn wait_with_timeout(child: &mut Child, timeout: Option<Duration>) -> io::Result<Exit> {
+    // A timeout too long to represent never expires
+    let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(finished(status, progress::cancel_requested()));
+        }
+        if progress::cancel_requested() {
+            return kill(child, Exit::Cancelled);
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return kill(child, Exit::TimedOut);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Runs `plugin` over `image` in a worker process
+///
+/// `image` is only replaced when the plugin succeeds. A worker that dies
+/// or does not answer within `timeout` is reported as an error and killed.
+pub fn process(
+    plugin: &Plugin,
+    image: &mut RgbaImage,
+    params: &CStr,
+    progress: &dyn Progress,
+    timeout: Option<Duration>,
+) -> Result<(), AppError> {
+    let mut child = Command::new(std::env::current_exe()?)
+        .arg(WORKER_COMMAND)
+        .arg(&plugin.path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+    let stdin = child.stdin.take().expect("worker stdin is piped");
+    let stdout = child.stdout.take().expect("worker stdout is piped");
+
+    let (width, height) = image.dimensions();
+    let pixels: &[u8] = image;
+    let (exit, response) = thread::scope(|scope| {
+        // A worker that crashes early closes its stdin, so write errors
+        // are ignored here and reported through the exit status instead
+        scope.spawn(move || write_request(stdin, width, height, params, pixels));
+        let reader = scope.spawn(move || read_response(stdout, progress));
+
+        let exit = wait_with_timeout(&mut child, timeout);
+        let response = reader.join().expect("worker reader thread panicked");
+        (exit, response)
+    });
+
+    let plugin_name = plugin.info.to_string();
+    match exit? {
+        Exit::Cancelled => Err(AppError::Cancelled),
+        Exit::TimedOut => Err(AppError::PluginTimedOut {
+            plugin: plugin_name,
+            timeout: timeout.unwrap_or_default(),
+        }),
+        Exit::Finished(status) if !status.success() => Err(AppError::PluginCrashed {
+            plugin: plugin_name,
+            status: status.to_string(),
+        }),
+        Exit::Finished(status) => {
+            let response = response.map_err(|_| AppError::PluginCrashed {
+                plugin: plugin_name.clone(),
+                status: format!("{status}, incomplete response"),
+            })?;
+            *image = response.into_image(plugin_name)?;
+            Ok(())
+        }
+    }
+}
+
+/// Final frame for the outcome of a plugin call that left `image`
+///
+/// Errors that do not come from the plugin, such as I/O errors, are
+/// returned as they are and end the worker without a final frame.
+fn worker_response(result: Result<(), AppError>, image: RgbaImage) -> Result<Response, AppError> {
+    let (status, message) = match result {
+        Ok(()) => {
+            return Ok(Response::Result {
+                status: 0,
+                message: String::new(),
+                image: Some(image),
+            });
+        }
+        Err(AppError::InvalidPluginOutput { reason, .. }) => {
+            return Ok(Response::InvalidOutput(reason));
+        }
+        Err(AppError::PluginFailed { code, message, .. }) => (code, message),
+        Err(AppError::PluginPanicked { message, .. }) => (ERROR_PANIC, message),
+        Err(AppError::Cancelled) => (ERROR_CANCELLED, String::new()),
+        Err(err) => return Err(err),
+    };
+    Ok(Response::Result {
+        status,
+        message,
+        image: None,
+    })
+}
+
+/// Progress of the plugin running in the worker, sent to the host as frames
+struct FrameProgress {
+    /// Last sent tenth of a percent and stage, to avoid flooding the pipe
+    last: Mutex<Option<(u32, String)>>,
+}
+
+impl Progress for FrameProgress {
+    fn report(&self, fraction: f32, stage: &str) {
+        let permille = (fraction.clamp(0.0, 1.0) * 1000.0) as u32;
+        let mut last = self.last.lock().unwrap_or_else(PoisonError::into_inner);
+        if last
+            .as_ref()
+            .is_some_and(|(sent, sent_stage)| *sent == permille && sent_stage == stage)
+        {
+            return;
+        }
+        *last = Some((permille, stage.to_owned()));
+        // The lock is held while writing, so frames never interleave. A
+        // broken pipe means the host is gone and will not read the result.
+        let _ = write_progress(io::stdout().lock(), fraction, stage);
+    }
+}
+
+/// Worker side: serves a single request from stdin for the library at `path`
+pub fn worker_main(path: &Path) -> Result<(), AppError> {
+    let plugin = Plugin::load(path)?;
+
+    let mut stdin = BufReader::new(io::stdin().lock());
+    let width = read_u32(&mut stdin)?;
+    let height = read_u32(&mut stdin)?;
+    let params_len = read_u32(&mut stdin)? as usize;
+    let params = read_bytes(&mut stdin, params_len)?;
+    let params = CString::new(params).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let pixels = read_bytes(&mut stdin, pixels_len(width, height)?)?;
+    let mut image =
+        RgbaImage::from_raw(width, height, pixels).expect("buffer length matches dimensions");
+
+    let progress = FrameProgress {
+        last: Mutex::new(None),
+    };
+    let result = plugin.apply(&mut image, &params, &progress);
+    let response = worker_response(result, image)?;
+
+    write_response(io::stdout().lock(), &response)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_round_trip() {
+        let mut bytes = Vec::new();
+        write_request(&mut bytes, 1, 2, c"{}", &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+
+        let mut reader = bytes.as_slice();
+        assert_eq!(read_u32(&mut reader).unwrap(), 1);
+        assert_eq!(read_u32(&mut reader).unwrap(), 2);
+        let params_len = read_u32(&mut reader).unwrap() as usize;
+        assert_eq!(read_bytes(&mut reader, params_len).unwrap(), b"{}");
+        assert_eq!(reader, [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    struct Recorder(Mutex<Vec<(f32, String)>>);
+
+    impl Progress for Recorder {
+        fn report(&self, fraction: f32, stage: &str) {
+            self.0.lock().unwrap().push((fraction, stage.to_owned()));
+        }
+    }
+
+    fn recorder() -> Recorder {
+        Recorder(Mutex::new(Vec::new()))
+    }
+
+    fn result(status: i32, message: &str, image: Option<RgbaImage>) -> Response {
+        Response::Result {
+            status,
+            message: message.to_owned(),
+            image,
+        }
+    }
+
+    #[test]
+    fn test_response_carries_new_dimensions() {
+        let image = RgbaImage::from_raw(1, 2, vec![1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        let mut bytes = Vec::new();
+        write_response(&mut bytes, &result(0, "", Some(image.clone()))).unwrap();
+
+        let response = read_response(bytes.as_slice(), &recorder()).unwrap();
+        assert_eq!(response.into_image("p 0.1.0".to_owned()).unwrap(), image);
+    }
+
+    #[test]
+    fn test_failed_response_has_no_pixels() {
+        let mut bytes = Vec::new();
+        write_response(&mut bytes, &result(-4, "bad", None)).unwrap();
+
+        let response = read_response(bytes.as_slice(), &recorder()).unwrap();
+        assert!(
+            matches!(&response, Response::Result { status: -4, message, image: None } if message == "bad"),
+            "{response:?}"
+        );
+    }
+
+    #[test]
+    fn test_truncated_response_is_an_error() {
+        let mut bytes = Vec::new();
+        write_response(&mut bytes, &result(0, "", Some(RgbaImage::new(2, 2)))).unwrap();
+        bytes.truncate(bytes.len() - 1);
+        assert!(read_response(bytes.as_slice(), &recorder()).is_err());
+    }
+
+    #[test]
+    fn test_progress_frames_precede_result() {
+        let mut bytes = Vec::new();
+        write_progress(&mut bytes, 0.5, "pass 1/2").unwrap();
+        write_progress(&mut bytes, 1.0, "pass 2/2").unwrap();
+        write_response(&mut bytes, &result(0, "", Some(RgbaImage::new(1, 1)))).unwrap();
+
+        let progress = recorder();
+        let response = read_response(bytes.as_slice(), &progress).unwrap();
+        assert!(matches!(response, Response::Result { status: 0, .. }));
+        assert_eq!(
+            *progress.0.lock().unwrap(),
+            vec![(0.5, "pass 1/2".to_owned()), (1.0, "pass 2/2".to_owned())]
+        );
+    }
+
+    /// Error the host reports for `result` of a plugin call in the worker
+    fn round_trip(result: Result<(), AppError>) -> AppError {
+        let response = worker_response(result, RgbaImage::new(1, 1)).unwrap();
+        let mut bytes = Vec::new();
+        write_response(&mut bytes, &response).unwrap();
+
+        let response = read_response(bytes.as_slice(), &recorder()).unwrap();
+        response.into_image("p 0.1.0".to_owned()).unwrap_err()
+    }
+
+    #[test]
+    fn test_cancelled_run_is_reported_as_cancelled() {
+        assert!(matches!(
+            round_trip(Err(AppError::Cancelled)),
+            AppError::Cancelled
+        ));
+    }
+
+    #[test]
+    fn test_invalid_output_is_not_a_crash() {
+        let err = round_trip(Err(AppError::InvalidPluginOutput {
+            plugin: "p 0.1.0".to_owned(),
+            reason: "pixel buffer is null".to_owned(),
+        }));
+        assert!(
+            matches!(&err, AppError::InvalidPluginOutput { reason, .. } if reason == "pixel buffer is null"),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn test_positive_plugin_code_is_a_plugin_failure() {
+        // Plugins may return any non-zero code; it must not be mistaken for
+        // the worker rejecting their output
+        let err = round_trip(Err(AppError::PluginFailed {
+            plugin: "p 0.1.0".to_owned(),
+            code: 1,
+            message: "custom".to_owned(),
+        }));
+        assert!(
+            matches!(&err, AppError::PluginFailed { code: 1, message, .. } if message == "custom"),
+            "{err:?}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failed_exit_after_ctrl_c_is_cancelled() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let failed = ExitStatus::from_raw(1 << 8);
+        assert!(matches!(finished(failed, true), Exit::Cancelled));
+        assert!(matches!(finished(failed, false), Exit::Finished(_)));
+        let success = ExitStatus::from_raw(0);
+        assert!(matches!(finished(success, true), Exit::Finished(_)));
+    }
+}
@@ -1,56 +1,276 @@
 //! Image processing application with plugin support
 
+mod batch;
+mod discovery;
 mod error;
+mod isolate;
+mod output;
+mod pipeline;
+mod pipeline_file;
 mod plugin_loader;
+mod progress;
+mod schema;
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
+use discovery::PluginSearchPath;
 use error::AppError;
-use plugin_loader::Plugin;
+use image::ImageFormat;
+use pipeline::{Execution, Pipeline, Step};
+use pipeline_file::PipelineFile;
 
-use image::{ImageBuffer, Rgba};
-use std::ffi::CString;
-use std::fs;
+use std::io::{self, IsTerminal};
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
 
 #[derive(Parser)]
-#[command(version, about="Image processing application with plugin support", long_about = None)]
+#[command(
+    version,
+    about = "Image processing application with plugin support",
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Cli {
-    input: PathBuf,
-    output: PathBuf,
-    plugin: String,
-    params: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    #[arg(long, default_value = "target/debug")]
-    plugin_path: PathBuf,
+    #[command(flatten)]
+    process: ProcessArgs,
 }
 
-fn main() -> Result<(), AppError> {
-    let cli = Cli::parse();
+#[derive(Subcommand)]
+enum Command {
+    /// Execute a pipeline description file (JSON or TOML)
+    Run(RunArgs),
+    /// Process every image of a directory or glob pattern in parallel
+    Batch(BatchArgs),
+    /// List every loadable plugin in the search path
+    ListPlugins(ListPluginsArgs),
+    /// Internal: serve one `--isolate` request for the given plugin library
+    #[command(hide = true)]
+    Worker(WorkerArgs),
+}
 
-    if !cli.input.exists() {
-        return Err(AppError::InputImageNotFound(cli.input));
-    }
-    if !cli.params.exists() {
-        return Err(AppError::ParamsFileNotFound(cli.params));
+#[derive(Args)]
+struct ExecutionArgs {
+    /// Run every plugin call in a separate worker process, so that a crash
+    /// inside the plugin is reported instead of killing the processor
+    #[arg(long)]
+    isolate: bool,
+
+    /// Abort a plugin call that runs longer than this many seconds: an
+    /// isolated worker is killed, an in-process plugin is asked to stop
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    timeout: Option<Duration>,
+}
+
+impl ExecutionArgs {
+    fn execution(&self) -> Execution {
+        if self.isolate {
+            Execution::Isolated {
+                timeout: self.timeout,
+            }
+        } else {
+            Execution::InProcess {
+                timeout: self.timeout,
+            }
+        }
     }
+}
+
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    let seconds: f64 = value.parse().map_err(|e| format!("{e}"))?;
+    Duration::try_from_secs_f64(seconds)
+        .ok()
+        .filter(|timeout| !timeout.is_zero())
+        .ok_or_else(|| format!("expected a positive number of seconds, got `{value}`"))
+}
+
+#[derive(Args)]
+struct PluginPathArgs {
+    /// Directory to search for plugins, may be repeated; searched before
+    /// IMAGE_PROCESSOR_PLUGIN_PATH, falls back to target/debug
+    #[arg(long = "plugin-path", value_name = "DIR")]
+    plugin_path: Vec<PathBuf>,
+}
+
+/// Top-level single-image mode; INPUT and OUTPUT are only required
+/// when no subcommand is given
+#[derive(Args)]
+struct ProcessArgs {
+    #[arg(required = true)]
+    input: Option<PathBuf>,
+    #[arg(required = true)]
+    output: Option<PathBuf>,
+    #[command(flatten)]
+    steps: StepArgs,
+}
 
-    let img = image::open(&cli.input)?.to_rgba8();
-    let (width, height) = img.dimensions();
-    let mut buffer = img.into_raw();
+#[derive(Args)]
+struct StepArgs {
+    #[arg(requires = "params")]
+    plugin: Option<String>,
+    params: Option<PathBuf>,
 
-    let params_text = fs::read_to_string(&cli.params)?;
-    let params_c = CString::new(params_text).expect("CString conversion failed");
+    /// Additional step applied after the positional plugin, may be repeated
+    #[arg(long = "step", value_name = "PLUGIN=PARAMS")]
+    steps: Vec<Step>,
 
-    let plugin = Plugin::load(&cli.plugin_path, &cli.plugin)?;
+    #[command(flatten)]
+    plugin_path: PluginPathArgs,
+
+    #[command(flatten)]
+    execution: ExecutionArgs,
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// Pipeline file, format is chosen by the `.json` or `.toml` extension
+    pipeline: PathBuf,
+
+    /// Replaces `plugin_path` from the pipeline file when given
+    #[command(flatten)]
+    plugin_path: PluginPathArgs,
+
+    #[command(flatten)]
+    execution: ExecutionArgs,
+}
+
+#[derive(Args)]
+struct WorkerArgs {
+    library: PathBuf,
+}
+
+#[derive(Args)]
+struct ListPluginsArgs {
+    #[command(flatten)]
+    plugin_path: PluginPathArgs,
+}
+
+#[derive(Args)]
+struct BatchArgs {
+    /// Input directory or glob pattern (quote it to keep the shell from expanding it)
+    input: String,
+    /// Directory the processed images are written to
+    output_dir: PathBuf,
+    #[command(flatten)]
+    steps: StepArgs,
+
+    /// Output file name template: {name}, {stem}, {ext} and {index} are substituted
+    #[arg(long, default_value = batch::DEFAULT_NAME_TEMPLATE)]
+    name: String,
+
+    /// Number of worker threads, defaults to the number of CPUs
+    #[arg(long)]
+    jobs: Option<usize>,
+}
 
-    unsafe {
-        (plugin.process_image)(width, height, buffer.as_mut_ptr(), params_c.as_ptr());
+impl StepArgs {
+    /// Loads the positional plugin followed by every `--step`
+    fn load_pipeline(self) -> Result<Pipeline, AppError> {
+        let mut steps = Vec::with_capacity(self.steps.len() + 1);
+        if let (Some(plugin), Some(params)) = (self.plugin, self.params) {
+            steps.push(Step { plugin, params });
+        }
+        steps.extend(self.steps);
+
+        let search = PluginSearchPath::new(self.plugin_path.plugin_path);
+        Ok(Pipeline::load(&search, &steps)?.with_execution(self.execution.execution()))
+    }
+}
+
+fn process(args: ProcessArgs) -> Result<(), AppError> {
+    let (Some(input), Some(output)) = (args.input, args.output) else {
+        unreachable!("clap requires INPUT and OUTPUT without a subcommand");
+    };
+    if !input.exists() {
+        return Err(AppError::InputImageNotFound(input));
     }
+    let format = ImageFormat::from_path(&output)?;
 
-    let out_img: ImageBuffer<Rgba<u8>, _> =
-        ImageBuffer::from_raw(width, height, buffer).expect("Invalid image buffer size");
+    let pipeline = args
+        .steps
+        .load_pipeline()?
+        .with_progress(io::stderr().is_terminal());
 
-    out_img.save(&cli.output)?;
+    let mut img = image::open(&input)?.to_rgba8();
+    pipeline.run(&mut img)?;
+
+    output::save(img, &output, format)?;
 
     Ok(())
 }
+
+fn run(args: RunArgs) -> Result<(), AppError> {
+    let file = PipelineFile::open(&args.pipeline)?;
+    file.execute(args.plugin_path.plugin_path, args.execution.execution())
+}
+
+fn list_plugins(args: ListPluginsArgs) -> Result<(), AppError> {
+    let search = PluginSearchPath::new(args.plugin_path.plugin_path);
+    let plugins = search.discover();
+
+    if plugins.is_empty() {
+        for dir in search.dirs() {
+            eprintln!("no plugin libraries in {}", dir.display());
+        }
+    }
+
+    for plugin in plugins {
+        match plugin {
+            Ok(plugin) => {
+                let info = &plugin.info;
+                let schema = serde_json::to_string_pretty(&info.params_schema)
+                    .expect("JSON value always serializes");
+                println!("{info} ({})", plugin.path.display());
+                println!("    {}", info.description);
+                println!("    parameters:");
+                for line in schema.lines() {
+                    println!("        {line}");
+                }
+            }
+            Err((path, err)) => eprintln!("skipping {}: {err}", path.display()),
+        }
+    }
+
+    Ok(())
+}
+
+fn batch(args: BatchArgs) -> Result<(), AppError> {
+    let inputs = batch::collect_inputs(&args.input)?;
+    let pipeline = args.steps.load_pipeline()?;
+    let jobs = args.jobs.unwrap_or_else(|| {
+        thread::available_parallelism()
+            .map(NonZeroUsize::get)
+            .unwrap_or(1)
+    });
+
+    let report = batch::run(&pipeline, inputs, &args.output_dir, &args.name, jobs)?;
+    report.print_summary();
+
+    if progress::cancel_requested() {
+        return Err(AppError::Cancelled);
+    }
+    match report.failed() {
+        0 => Ok(()),
+        failed => Err(AppError::BatchFailed {
+            failed,
+            total: report.entries.len(),
+        }),
+    }
+}
+
+fn main() -> Result<(), AppError> {
+    let cli = Cli::parse();
+    progress::install_interrupt_handler()?;
+
+    match cli.command {
+        Some(Command::Run(args)) => run(args),
+        Some(Command::Batch(args)) => batch(args),
+        Some(Command::ListPlugins(args)) => list_plugins(args),
+        Some(Command::Worker(args)) => isolate::worker_main(&args.library),
+        None => process(cli.process),
+    }
+}
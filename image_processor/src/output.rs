@@ -0,0 +1,77 @@
+//! Saving processed images
+
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use std::path::{Path, PathBuf};
+
+use crate::error::AppError;
+
+/// Saves `img` to `path` in `format`
+///
+/// Formats without an alpha channel, such as JPEG, get the RGB channels only
+/// instead of failing on the RGBA buffer.
+///
+/// The image is written to a temporary file next to `path` and then renamed
+/// over it, so an exit in the middle of saving, e.g. on a second Ctrl-C,
+/// never leaves a truncated output behind.
+pub fn save(img: RgbaImage, path: &Path, format: ImageFormat) -> Result<(), AppError> {
+    let temp = temp_path(path);
+    let saved = if format == ImageFormat::Jpeg {
+        DynamicImage::ImageRgba8(img)
+            .to_rgb8()
+            .save_with_format(&temp, format)
+    } else {
+        img.save_with_format(&temp, format)
+    };
+    if let Err(e) = saved {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    std::fs::rename(&temp, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&temp);
+    })?;
+    Ok(())
+}
+
+/// Hidden sibling of `path`, unique per process so parallel runs do not clash
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.{}.tmp", std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jpeg_drops_alpha() {
+        let dir =
+            std::env::temp_dir().join(format!("image_processor_output_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.jpg");
+        let img = RgbaImage::from_pixel(2, 2, image::Rgba([200, 100, 50, 128]));
+
+        save(img, &path, ImageFormat::Jpeg).unwrap();
+        let saved = image::open(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(saved.color(), image::ColorType::Rgb8);
+    }
+
+    #[test]
+    fn test_replaces_output_without_leftovers() {
+        let dir = std::env::temp_dir().join(format!(
+            "image_processor_output_replace_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.png");
+        std::fs::write(&path, b"old contents").unwrap();
+        let img = RgbaImage::from_pixel(3, 1, image::Rgba([1, 2, 3, 4]));
+
+        save(img.clone(), &path, ImageFormat::Png).unwrap();
+        let saved = image::open(&path).unwrap().to_rgba8();
+        let entries = std::fs::read_dir(&dir).unwrap().count();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(saved, img);
+        assert_eq!(entries, 1, "temporary file left behind");
+    }
+}
@@ -0,0 +1,238 @@
+use image::RgbaImage;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::discovery::PluginSearchPath;
+use crate::error::AppError;
+use crate::isolate;
+use crate::plugin_loader::Plugin;
+use crate::progress::{self, Deadline, ProgressBar};
+use crate::schema;
+
+/// A single plugin invocation: plugin name and its parameters file
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub plugin: String,
+    pub params: PathBuf,
+}
+
+impl FromStr for Step {
+    type Err = String;
+
+    /// Parses `plugin=params.json`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (plugin, params) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected PLUGIN=PARAMS, got `{s}`"))?;
+        if plugin.is_empty() || params.is_empty() {
+            return Err(format!("expected PLUGIN=PARAMS, got `{s}`"));
+        }
+        Ok(Self {
+            plugin: plugin.to_owned(),
+            params: PathBuf::from(params),
+        })
+    }
+}
+
+/// Step whose params are already in memory, `origin` names them in errors
+///
+/// With `base_dir`, relative paths in params marked `"format": "path"` in
+/// the plugin schema are resolved against it; otherwise they stay relative
+/// to the working directory.
+pub struct PreparedStep<'a> {
+    pub plugin: &'a str,
+    pub origin: String,
+    pub params: CString,
+    pub base_dir: Option<&'a Path>,
+}
+
+/// Checks params against the schema exported by the plugin and returns
+/// them in the form the plugin expects: integral floats in `integer` fields
+/// become integers, and paths are resolved against `base_dir`
+fn validate_params(plugin: &Plugin, step: &PreparedStep) -> Result<CString, AppError> {
+    let invalid = |errors| AppError::InvalidParams {
+        plugin: plugin.info.name.clone(),
+        origin: step.origin.clone(),
+        errors,
+    };
+    let schema = &plugin.info.params_schema;
+    let mut value: serde_json::Value = serde_json::from_str(step.params.to_str()?)
+        .map_err(|e| invalid(vec![format!("not valid JSON: {e}")]))?;
+    schema::validate(schema, &value).map_err(invalid)?;
+    schema::normalize_integers(schema, &mut value);
+    if let Some(base) = step.base_dir {
+        schema::resolve_paths(schema, &mut value, base);
+    }
+    Ok(CString::new(value.to_string()).expect("serialized JSON never contains NUL bytes"))
+}
+
+/// Loaded step: index into the plugin list plus C-compatible params
+struct Stage {
+    plugin: usize,
+    params: CString,
+}
+
+/// Where plugin code runs and how long a single plugin call may take
+#[derive(Debug, Clone, Copy)]
+pub enum Execution {
+    /// Directly in the host process; after `timeout` the plugin is asked to
+    /// stop through the cancellation flag and must check it to be aborted
+    InProcess { timeout: Option<Duration> },
+    /// In a worker process per call, killed after `timeout` if given
+    Isolated { timeout: Option<Duration> },
+}
+
+impl Default for Execution {
+    fn default() -> Self {
+        Execution::InProcess { timeout: None }
+    }
+}
+
+/// Sequence of plugins applied to the same RGBA buffer
+///
+/// Every distinct plugin library is opened once, even if it appears in
+/// several steps.
+pub struct Pipeline {
+    plugins: Vec<Plugin>,
+    stages: Vec<Stage>,
+    execution: Execution,
+    show_progress: bool,
+}
+
+impl Pipeline {
+    /// Reads params files and loads the plugins required by `steps`
+    pub fn load(search: &PluginSearchPath, steps: &[Step]) -> Result<Self, AppError> {
+        let mut resolved = Vec::with_capacity(steps.len());
+        for step in steps {
+            if !step.params.exists() {
+                return Err(AppError::ParamsFileNotFound(step.params.clone()));
+            }
+            let params_text = fs::read_to_string(&step.params)?;
+            let params = CString::new(params_text)
+                .map_err(|_| AppError::ParamsContainNul(step.params.clone()))?;
+            resolved.push(PreparedStep {
+                plugin: &step.plugin,
+                origin: step.params.display().to_string(),
+                params,
+                base_dir: None,
+            });
+        }
+        Self::build(search, resolved)
+    }
+
+    /// Loads the plugins for already prepared steps and validates their params
+    pub fn build<'a>(
+        search: &PluginSearchPath,
+        steps: impl IntoIterator<Item = PreparedStep<'a>>,
+    ) -> Result<Self, AppError> {
+        let mut plugins = Vec::new();
+        let mut loaded: HashMap<&str, usize> = HashMap::new();
+        let mut stages = Vec::new();
+
+        for step in steps {
+            let plugin = match loaded.get(step.plugin) {
+                Some(&index) => index,
+                None => {
+                    plugins.push(search.load(step.plugin)?);
+                    loaded.insert(step.plugin, plugins.len() - 1);
+                    plugins.len() - 1
+                }
+            };
+            let params = validate_params(&plugins[plugin], &step)?;
+            stages.push(Stage { plugin, params });
+        }
+
+        if stages.is_empty() {
+            return Err(AppError::EmptyPipeline);
+        }
+
+        Ok(Self {
+            plugins,
+            stages,
+            execution: Execution::default(),
+            show_progress: false,
+        })
+    }
+
+    /// Selects where the plugins are executed
+    pub fn with_execution(mut self, execution: Execution) -> Self {
+        self.execution = execution;
+        self
+    }
+
+    /// Draws a progress bar on stderr while each step runs
+    pub fn with_progress(mut self, show_progress: bool) -> Self {
+        self.show_progress = show_progress;
+        self
+    }
+
+    /// Passes the image through every step in order, stopping at the first failure
+    ///
+    /// A step may replace `image` with one of a different size, and the next
+    /// step receives the new dimensions. Cancellation is checked between
+    /// steps as well as by the plugins themselves.
+    pub fn run(&self, image: &mut RgbaImage) -> Result<(), AppError> {
+        for (index, stage) in self.stages.iter().enumerate() {
+            if progress::cancel_requested() {
+                return Err(AppError::Cancelled);
+            }
+            let plugin = &self.plugins[stage.plugin];
+            let label = format!("[{}/{}] {}", index + 1, self.stages.len(), plugin.info);
+            let bar = ProgressBar::new(label, self.show_progress);
+            match self.execution {
+                Execution::InProcess { timeout: None } => {
+                    plugin.apply(image, &stage.params, &bar)?
+                }
+                Execution::InProcess {
+                    timeout: Some(timeout),
+                } => {
+                    let deadline = Deadline::new(&bar, timeout);
+                    match plugin.apply(image, &stage.params, &deadline) {
+                        Err(AppError::Cancelled) if deadline.expired() => {
+                            return Err(AppError::PluginTimedOut {
+                                plugin: plugin.info.to_string(),
+                                timeout,
+                            });
+                        }
+                        result => result?,
+                    }
+                }
+                Execution::Isolated { timeout } => {
+                    isolate::process(plugin, image, &stage.params, &bar, timeout)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_step() {
+        let step: Step = "blur_plugin=blur_params.json".parse().unwrap();
+        assert_eq!(step.plugin, "blur_plugin");
+        assert_eq!(step.params, PathBuf::from("blur_params.json"));
+    }
+
+    #[test]
+    fn test_parse_step_rejects_missing_params() {
+        assert!("blur_plugin".parse::<Step>().is_err());
+        assert!("blur_plugin=".parse::<Step>().is_err());
+        assert!("=blur_params.json".parse::<Step>().is_err());
+    }
+
+    #[test]
+    fn test_empty_pipeline() {
+        assert!(matches!(
+            Pipeline::load(&PluginSearchPath::new(Vec::new()), &[]),
+            Err(AppError::EmptyPipeline)
+        ));
+    }
+}
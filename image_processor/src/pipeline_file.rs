@@ -0,0 +1,206 @@
+use image::ImageFormat;
+use serde::Deserialize;
+use std::ffi::CString;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+
+use crate::discovery::PluginSearchPath;
+use crate::error::AppError;
+use crate::output;
+use crate::pipeline::{Execution, Pipeline, PreparedStep};
+
+/// Declarative editing recipe: input image, ordered steps and output settings
+///
+/// Relative paths inside the file are resolved against the directory that
+/// contains the file, so recipes can be kept next to their assets. This
+/// includes step params that the plugin schema marks as `"format": "path"`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineFile {
+    pub input: PathBuf,
+    pub output: OutputSettings,
+    #[serde(default)]
+    pub plugin_path: Option<PathBuf>,
+    pub steps: Vec<PipelineStep>,
+
+    #[serde(skip)]
+    base_dir: PathBuf,
+    #[serde(skip)]
+    source: PathBuf,
+}
+
+/// Where and how the processed image is written
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputSettings {
+    pub path: PathBuf,
+    /// Image format name (`png`, `jpeg`, `bmp`, ...), inferred from `path` when omitted
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+/// One step of the recipe: plugin name and its inline params object
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineStep {
+    pub plugin: String,
+    #[serde(default = "empty_params")]
+    pub params: serde_json::Value,
+}
+
+fn empty_params() -> serde_json::Value {
+    serde_json::Value::Object(serde_json::Map::new())
+}
+
+impl PipelineFile {
+    /// Reads a pipeline file, choosing the parser by its extension
+    pub fn open(path: &Path) -> Result<Self, AppError> {
+        if !path.exists() {
+            return Err(AppError::PipelineFileNotFound(path.to_path_buf()));
+        }
+        let text = fs::read_to_string(path)?;
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+
+        let mut file = match extension.as_deref() {
+            Some("json") => Self::from_json(&text),
+            Some("toml") => Self::from_toml(&text),
+            _ => Err("expected a .json or .toml extension".to_owned()),
+        }
+        .map_err(|reason| AppError::InvalidPipelineFile {
+            path: path.to_path_buf(),
+            reason,
+        })?;
+
+        file.base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        file.source = path.to_path_buf();
+        Ok(file)
+    }
+
+    fn from_json(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|e| e.to_string())
+    }
+
+    fn from_toml(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    fn resolve(&self, path: &Path) -> PathBuf {
+        self.base_dir.join(path)
+    }
+
+    /// Loads the plugins and runs the recipe from input to output
+    ///
+    /// Non-empty `plugin_dirs` from the command line replace `plugin_path`
+    /// from the file.
+    pub fn execute(&self, plugin_dirs: Vec<PathBuf>, execution: Execution) -> Result<(), AppError> {
+        let input = self.resolve(&self.input);
+        if !input.exists() {
+            return Err(AppError::InputImageNotFound(input));
+        }
+
+        let output = self.resolve(&self.output.path);
+        let format = match &self.output.format {
+            Some(name) => ImageFormat::from_extension(name)
+                .ok_or_else(|| AppError::UnsupportedOutputFormat(name.clone()))?,
+            None => ImageFormat::from_path(&output)?,
+        };
+
+        let plugin_dirs = if plugin_dirs.is_empty() {
+            self.plugin_path
+                .iter()
+                .map(|dir| self.resolve(dir))
+                .collect()
+        } else {
+            plugin_dirs
+        };
+        let search = PluginSearchPath::new(plugin_dirs);
+
+        let steps = self.steps.iter().enumerate().map(|(index, step)| {
+            // Serialized JSON escapes NUL, so the conversion cannot fail
+            let params = CString::new(step.params.to_string())
+                .expect("serialized JSON never contains NUL bytes");
+            PreparedStep {
+                plugin: &step.plugin,
+                origin: format!("step {} of {}", index + 1, self.source.display()),
+                params,
+                base_dir: Some(&self.base_dir),
+            }
+        });
+        let pipeline = Pipeline::build(&search, steps)?
+            .with_execution(execution)
+            .with_progress(io::stderr().is_terminal());
+
+        let mut img = image::open(&input)?.to_rgba8();
+        pipeline.run(&mut img)?;
+
+        output::save(img, &output, format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_toml() {
+        let file = PipelineFile::from_toml(
+            r#"
+            input = "input.png"
+            plugin_path = "target/debug"
+
+            [output]
+            path = "output.png"
+
+            [[steps]]
+            plugin = "blur_plugin"
+            params = { radius = 2, iterations = 3 }
+
+            [[steps]]
+            plugin = "mirror_plugin"
+            params = { horizontal = true, vertical = false }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(file.input, PathBuf::from("input.png"));
+        assert_eq!(file.output.path, PathBuf::from("output.png"));
+        assert_eq!(file.steps.len(), 2);
+        assert_eq!(file.steps[0].plugin, "blur_plugin");
+        assert_eq!(
+            file.steps[0].params,
+            serde_json::json!({"radius": 2, "iterations": 3})
+        );
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let file = PipelineFile::from_json(
+            r#"{
+                "input": "input.png",
+                "output": {"path": "output.jpg", "format": "jpeg"},
+                "steps": [{"plugin": "mirror_plugin"}]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(file.output.format.as_deref(), Some("jpeg"));
+        assert_eq!(file.plugin_path, None);
+        assert_eq!(file.steps[0].params, empty_params());
+    }
+
+    #[test]
+    fn test_unknown_field_rejected() {
+        let result = PipelineFile::from_toml(
+            r#"
+            input = "input.png"
+            output = { path = "output.png" }
+            step = []
+            "#,
+        );
+        assert!(result.is_err());
+    }
+}
@@ -1,50 +1,344 @@
+use image::RgbaImage;
 use libloading::Library;
-use std::path::Path;
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
 
 use crate::error::AppError;
+use crate::progress::{self, Progress};
 
-pub type ProcessImageFn = unsafe extern "C" fn(
-    width: u32,
-    height: u32,
-    rgba_data: *mut u8,
-    params: *const std::os::raw::c_char,
-);
+use plugin_sdk::abi::{
+    ABI_VERSION, CAPABILITY_IN_PLACE, CAPABILITY_TRANSFORM, ERROR_CANCELLED, ERROR_PANIC,
+    FreeImageFn, LastErrorFn, OutputImage, PIXEL_FORMAT_RGBA8, PluginDescriptorFn, ProcessImageFn,
+    TransformImageFn,
+};
+
+/// Plugin metadata copied out of the descriptor
+#[derive(Debug, Clone)]
+pub struct PluginInfo {
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    pub params_schema: serde_json::Value,
+}
+
+impl fmt::Display for PluginInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.version)
+    }
+}
+
+/// Entry point the plugin processes images through
+enum EntryPoint {
+    /// `process_image`: modifies the buffer in place
+    InPlace(ProcessImageFn),
+    /// `transform_image`: returns a new buffer, released with `free_image`
+    Transform {
+        transform: TransformImageFn,
+        free: FreeImageFn,
+    },
+}
 
 pub struct Plugin {
     _lib: Library,
-    pub process_image: ProcessImageFn,
+    pub path: PathBuf,
+    pub info: PluginInfo,
+    entry: EntryPoint,
+    last_error: Option<LastErrorFn>,
 }
 
-fn platform_library_name(name: &str) -> String {
-    if cfg!(target_os = "windows") {
-        format!("{name}.dll")
-    } else if cfg!(target_os = "macos") {
-        format!("lib{name}.dylib")
-    } else {
-        format!("lib{name}.so")
+/// Copies a descriptor string, rejecting null pointers and invalid UTF-8
+unsafe fn descriptor_string(
+    lib_path: &Path,
+    field: &str,
+    ptr: *const c_char,
+) -> Result<String, AppError> {
+    if ptr.is_null() {
+        return Err(AppError::InvalidDescriptor {
+            path: lib_path.to_path_buf(),
+            reason: format!("`{field}` is null"),
+        });
     }
+    let value =
+        unsafe { CStr::from_ptr(ptr) }
+            .to_str()
+            .map_err(|_| AppError::InvalidDescriptor {
+                path: lib_path.to_path_buf(),
+                reason: format!("`{field}` is not valid UTF-8"),
+            })?;
+    Ok(value.to_owned())
 }
 
-impl Plugin {
-    /// Loads a plugin from the specified directory and name
-    pub fn load(plugin_dir: &Path, plugin_name: &str) -> Result<Self, AppError> {
-        let lib_name = platform_library_name(plugin_name);
-        let lib_path = plugin_dir.join(lib_name);
+/// Reads and validates the descriptor of an already opened library
+///
+/// Returns the plugin metadata and its `capabilities` bit mask.
+fn read_descriptor(lib: &Library, lib_path: &Path) -> Result<(PluginInfo, u32), AppError> {
+    let descriptor_fn = unsafe {
+        let symbol: libloading::Symbol<PluginDescriptorFn> = lib
+            .get(b"plugin_descriptor\0")
+            .map_err(|_| AppError::MissingDescriptor(lib_path.to_path_buf()))?;
+        *symbol
+    };
+
+    let descriptor = unsafe { descriptor_fn() };
+    if descriptor.is_null() {
+        return Err(AppError::InvalidDescriptor {
+            path: lib_path.to_path_buf(),
+            reason: "descriptor pointer is null".to_owned(),
+        });
+    }
 
-        if !lib_path.exists() {
-            return Err(AppError::PluginNotFound(lib_path));
+    // Only the leading version field is read until it is known to match
+    let abi_version = unsafe { std::ptr::addr_of!((*descriptor).abi_version).read() };
+    if abi_version != ABI_VERSION {
+        return Err(AppError::IncompatibleAbi {
+            path: lib_path.to_path_buf(),
+            expected: ABI_VERSION,
+            found: abi_version,
+        });
+    }
+
+    let descriptor = unsafe { &*descriptor };
+    let params_schema =
+        unsafe { descriptor_string(lib_path, "params_schema", descriptor.params_schema)? };
+    let params_schema =
+        serde_json::from_str(&params_schema).map_err(|e| AppError::InvalidDescriptor {
+            path: lib_path.to_path_buf(),
+            reason: format!("`params_schema` is not valid JSON: {e}"),
+        })?;
+
+    let info = unsafe {
+        PluginInfo {
+            name: descriptor_string(lib_path, "name", descriptor.name)?,
+            version: descriptor_string(lib_path, "version", descriptor.version)?,
+            description: descriptor_string(lib_path, "description", descriptor.description)?,
+            params_schema,
         }
+    };
+
+    if descriptor.pixel_formats & PIXEL_FORMAT_RGBA8 == 0 {
+        return Err(AppError::UnsupportedPixelFormat { plugin: info.name });
+    }
+
+    Ok((info, descriptor.capabilities))
+}
 
-        let lib = unsafe { Library::new(&lib_path)? };
+impl Plugin {
+    /// Loads a plugin library from the given path
+    pub fn load(lib_path: &Path) -> Result<Self, AppError> {
+        let lib = unsafe { Library::new(lib_path)? };
+
+        let (info, capabilities) = read_descriptor(&lib, lib_path)?;
+
+        let entry = if capabilities & CAPABILITY_TRANSFORM != 0 {
+            unsafe {
+                EntryPoint::Transform {
+                    transform: *lib.get::<TransformImageFn>(b"transform_image\0")?,
+                    free: *lib.get::<FreeImageFn>(b"free_image\0")?,
+                }
+            }
+        } else if capabilities & CAPABILITY_IN_PLACE != 0 {
+            EntryPoint::InPlace(unsafe { *lib.get::<ProcessImageFn>(b"process_image\0")? })
+        } else {
+            return Err(AppError::InvalidDescriptor {
+                path: lib_path.to_path_buf(),
+                reason: "`capabilities` names no supported entry point".to_owned(),
+            });
+        };
 
-        let process_image = unsafe {
-            let symbol: libloading::Symbol<ProcessImageFn> = lib.get(b"process_image\0")?;
-            *symbol
+        let last_error = unsafe {
+            lib.get::<LastErrorFn>(b"plugin_last_error\0")
+                .ok()
+                .map(|symbol| *symbol)
         };
 
         Ok(Self {
             _lib: lib,
-            process_image,
+            path: lib_path.to_path_buf(),
+            info,
+            entry,
+            last_error,
         })
     }
+
+    /// Reads the plugin's error message for the current thread, if it has one
+    fn last_error_message(&self) -> String {
+        let Some(last_error) = self.last_error else {
+            return String::new();
+        };
+        let message = unsafe { last_error() };
+        if message.is_null() {
+            return String::new();
+        }
+        unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Runs the plugin over `image`, reporting to `progress`
+    ///
+    /// Transforming plugins may replace `image` with one of a different size.
+    pub fn apply(
+        &self,
+        image: &mut RgbaImage,
+        params: &CStr,
+        progress: &dyn Progress,
+    ) -> Result<(), AppError> {
+        let (width, height) = image.dimensions();
+        let context = progress::host_context(&progress);
+        match self.entry {
+            EntryPoint::InPlace(process_image) => {
+                let status = unsafe {
+                    process_image(width, height, image.as_mut_ptr(), params.as_ptr(), &context)
+                };
+                self.check_status(status)
+            }
+            EntryPoint::Transform { transform, free } => {
+                let mut output = OutputImage::default();
+                let status = unsafe {
+                    transform(
+                        width,
+                        height,
+                        image.as_ptr(),
+                        params.as_ptr(),
+                        &context,
+                        &mut output,
+                    )
+                };
+                if let Err(err) = self.check_status(status) {
+                    discard_output(&output, free);
+                    return Err(err);
+                }
+                *image = self.take_output(output, free)?;
+                Ok(())
+            }
+        }
+    }
+
+    fn check_status(&self, status: i32) -> Result<(), AppError> {
+        if status != 0 {
+            return Err(status_error(
+                self.info.to_string(),
+                status,
+                self.last_error_message(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Copies a `transform_image` result into an image and releases the plugin's buffer
+    fn take_output(&self, output: OutputImage, free: FreeImageFn) -> Result<RgbaImage, AppError> {
+        let expected = u64::from(output.width) * u64::from(output.height) * 4;
+        let pixels = if output.len as u64 != expected {
+            Err(format!(
+                "{} bytes for a {}x{} image",
+                output.len, output.width, output.height
+            ))
+        } else if output.data.is_null() && output.len > 0 {
+            Err("pixel buffer is null".to_owned())
+        } else if output.len == 0 {
+            Ok(Vec::new())
+        } else {
+            Ok(unsafe { std::slice::from_raw_parts(output.data, output.len) }.to_vec())
+        };
+        unsafe { free(output.data, output.len) };
+
+        pixels
+            .and_then(|pixels| {
+                RgbaImage::from_raw(output.width, output.height, pixels)
+                    .ok_or_else(|| "pixel buffer does not match the image size".to_owned())
+            })
+            .map_err(|reason| AppError::InvalidPluginOutput {
+                plugin: self.info.to_string(),
+                reason,
+            })
+    }
+}
+
+/// Releases a buffer a failed `transform_image` call left in `output`
+///
+/// The ABI asks plugins to leave `output` untouched on failure; this keeps a
+/// plugin that fills it anyway from leaking the buffer.
+fn discard_output(output: &OutputImage, free: FreeImageFn) {
+    if !output.data.is_null() {
+        unsafe { free(output.data, output.len) };
+    }
+}
+
+/// Converts a non-zero `process_image` status into the matching error
+pub fn status_error(plugin: String, code: i32, message: String) -> AppError {
+    match code {
+        ERROR_PANIC => AppError::PluginPanicked { plugin, message },
+        ERROR_CANCELLED => AppError::Cancelled,
+        _ => AppError::PluginFailed {
+            plugin,
+            code,
+            message,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static FREED: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe extern "C" fn counting_free(data: *mut u8, len: usize) {
+        drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(data, len)) });
+        FREED.fetch_add(len, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_failed_transform_output_is_freed() {
+        // A buffer the plugin filled despite failing goes back through free_image
+        let pixels = Box::into_raw(vec![0u8; 8].into_boxed_slice()).cast::<u8>();
+        let output = OutputImage {
+            width: 2,
+            height: 1,
+            data: pixels,
+            len: 8,
+        };
+        discard_output(&output, counting_free);
+        assert_eq!(FREED.load(Ordering::Relaxed), 8);
+
+        // A null buffer is not handed to the plugin
+        discard_output(&OutputImage::default(), counting_free);
+        assert_eq!(FREED.load(Ordering::Relaxed), 8);
+    }
+
+    #[test]
+    fn test_panic_status_is_reported_as_panic() {
+        let err = status_error(
+            "blur_plugin 0.1.0".to_owned(),
+            ERROR_PANIC,
+            "паника в плагине: index out of bounds".to_owned(),
+        );
+        assert!(matches!(err, AppError::PluginPanicked { .. }));
+        assert_eq!(
+            err.to_string(),
+            "Plugin `blur_plugin 0.1.0` panicked: паника в плагине: index out of bounds"
+        );
+    }
+
+    #[test]
+    fn test_cancelled_status() {
+        let err = status_error(
+            "blur_plugin 0.1.0".to_owned(),
+            ERROR_CANCELLED,
+            String::new(),
+        );
+        assert!(matches!(err, AppError::Cancelled));
+    }
+
+    #[test]
+    fn test_other_status_is_reported_as_failure() {
+        let err = status_error("mirror_plugin 0.1.0".to_owned(), -4, String::new());
+        assert_eq!(
+            err.to_string(),
+            "Plugin `mirror_plugin 0.1.0` failed with status -4: no details"
+        );
+    }
 }
@@ -0,0 +1,252 @@
+//! Progress reporting and cancellation of plugin calls
+//!
+//! Plugins receive a `HostContext` whose callbacks forward to a [`Progress`]
+//! implementation: a terminal progress bar in the host, or progress frames
+//! sent back over the pipe in an `--isolate` worker.
+
+use std::ffi::CStr;
+use std::io::{self, Write};
+use std::os::raw::{c_char, c_void};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use plugin_sdk::abi::HostContext;
+
+use crate::error::AppError;
+
+/// Width of the bar itself, in characters
+const BAR_WIDTH: usize = 30;
+
+/// Set once the user asked to stop, e.g. with Ctrl-C
+static CANCEL_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the user asked to stop processing
+pub fn cancel_requested() -> bool {
+    CANCEL_REQUESTED.load(Ordering::Relaxed)
+}
+
+/// Makes Ctrl-C cancel the running plugin instead of killing the process
+///
+/// A second Ctrl-C exits immediately, for plugins that never check for
+/// cancellation. Outputs are saved through a temporary file (see
+/// [`crate::output::save`]), so such an exit cannot truncate them.
+pub fn install_interrupt_handler() -> Result<(), AppError> {
+    ctrlc::set_handler(|| {
+        if CANCEL_REQUESTED.swap(true, Ordering::Relaxed) {
+            std::process::exit(130);
+        }
+    })?;
+    Ok(())
+}
+
+/// Receiver of progress reports from a running plugin
+///
+/// Plugins may call in from several threads at once.
+pub trait Progress: Sync {
+    /// Records that `fraction` (0 to 1) of the work is done, in `stage`
+    fn report(&self, fraction: f32, stage: &str);
+
+    /// Whether the plugin should stop as soon as possible
+    fn is_cancelled(&self) -> bool {
+        cancel_requested()
+    }
+}
+
+/// Asks the plugin to stop once a timeout has passed, on top of `progress`
+pub struct Deadline<'a> {
+    progress: &'a dyn Progress,
+    /// `None` when the timeout is too long to be represented
+    at: Option<Instant>,
+}
+
+impl<'a> Deadline<'a> {
+    /// Starts counting `timeout` from now
+    pub fn new(progress: &'a dyn Progress, timeout: Duration) -> Self {
+        Self {
+            progress,
+            at: Instant::now().checked_add(timeout),
+        }
+    }
+
+    /// Whether the timeout has passed
+    pub fn expired(&self) -> bool {
+        self.at.is_some_and(|at| Instant::now() >= at)
+    }
+}
+
+impl Progress for Deadline<'_> {
+    fn report(&self, fraction: f32, stage: &str) {
+        self.progress.report(fraction, stage);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.expired() || self.progress.is_cancelled()
+    }
+}
+
+unsafe extern "C" fn report_progress(user_data: *mut c_void, fraction: f32, stage: *const c_char) {
+    let progress = unsafe { &*(user_data as *const &dyn Progress) };
+    let stage = if stage.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(stage) }
+            .to_string_lossy()
+            .into_owned()
+    };
+    progress.report(fraction, &stage);
+}
+
+unsafe extern "C" fn is_cancelled(user_data: *mut c_void) -> i32 {
+    let progress = unsafe { &*(user_data as *const &dyn Progress) };
+    i32::from(progress.is_cancelled())
+}
+
+/// Builds the context passed to a plugin call
+///
+/// The context points at `progress`, so it must not outlive the borrow.
+pub fn host_context(progress: &&dyn Progress) -> HostContext {
+    HostContext {
+        user_data: progress as *const &dyn Progress as *mut c_void,
+        report_progress: Some(report_progress),
+        is_cancelled: Some(is_cancelled),
+    }
+}
+
+struct BarState {
+    percent: Option<u32>,
+    stage: String,
+}
+
+/// Single-line progress bar on stderr for one plugin call
+///
+/// The line is only redrawn when the percentage or the stage changes, and
+/// it is erased when the bar is dropped.
+pub struct ProgressBar {
+    label: String,
+    visible: bool,
+    state: Mutex<BarState>,
+}
+
+impl ProgressBar {
+    pub fn new(label: String, visible: bool) -> Self {
+        Self {
+            label,
+            visible,
+            state: Mutex::new(BarState {
+                percent: None,
+                stage: String::new(),
+            }),
+        }
+    }
+}
+
+/// Formats a progress line, without the leading carriage return
+fn render(label: &str, percent: u32, stage: &str) -> String {
+    let filled = BAR_WIDTH * percent as usize / 100;
+    let mut line = format!(
+        "{label} [{}{}] {percent:>3}%",
+        "#".repeat(filled),
+        ".".repeat(BAR_WIDTH - filled)
+    );
+    if !stage.is_empty() {
+        line.push(' ');
+        line.push_str(stage);
+    }
+    line
+}
+
+impl Progress for ProgressBar {
+    fn report(&self, fraction: f32, stage: &str) {
+        if !self.visible {
+            return;
+        }
+        let percent = (fraction.clamp(0.0, 1.0) * 100.0) as u32;
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.percent == Some(percent) && state.stage == stage {
+            return;
+        }
+        state.percent = Some(percent);
+        state.stage.clear();
+        state.stage.push_str(stage);
+        // A closed stderr must not abort the plugin call
+        let _ = write!(
+            io::stderr(),
+            "\r{}\x1b[K",
+            render(&self.label, percent, stage)
+        );
+    }
+}
+
+impl Drop for ProgressBar {
+    fn drop(&mut self) {
+        let state = self.state.get_mut().unwrap_or_else(PoisonError::into_inner);
+        if state.percent.is_some() {
+            let _ = write!(io::stderr(), "\r\x1b[K");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Recorder(Mutex<Vec<(f32, String)>>, bool);
+
+    impl Progress for Recorder {
+        fn report(&self, fraction: f32, stage: &str) {
+            self.0.lock().unwrap().push((fraction, stage.to_owned()));
+        }
+
+        fn is_cancelled(&self) -> bool {
+            self.1
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render("[1/2] blur_plugin 0.1.0", 50, "pass 1/3"),
+            format!(
+                "[1/2] blur_plugin 0.1.0 [{}{}]  50% pass 1/3",
+                "#".repeat(15),
+                ".".repeat(15)
+            )
+        );
+        assert_eq!(render("x", 100, ""), format!("x [{}] 100%", "#".repeat(30)));
+    }
+
+    #[test]
+    fn test_host_context_forwards_to_progress() {
+        let recorder = Recorder(Mutex::new(Vec::new()), true);
+        let progress: &dyn Progress = &recorder;
+        let context = host_context(&progress);
+
+        unsafe {
+            (context.report_progress.unwrap())(context.user_data, 0.25, c"pass 1/3".as_ptr());
+            assert_eq!((context.is_cancelled.unwrap())(context.user_data), 1);
+        }
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![(0.25, "pass 1/3".to_owned())]
+        );
+    }
+
+    #[test]
+    fn test_deadline() {
+        let recorder = Recorder(Mutex::new(Vec::new()), false);
+
+        let deadline = Deadline::new(&recorder, Duration::ZERO);
+        assert!(deadline.expired());
+        assert!(deadline.is_cancelled());
+
+        let deadline = Deadline::new(&recorder, Duration::MAX);
+        assert!(!deadline.expired());
+        assert!(!deadline.is_cancelled());
+        deadline.report(0.5, "pass 1/2");
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![(0.5, "pass 1/2".to_owned())]
+        );
+    }
+}
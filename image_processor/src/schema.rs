@@ -0,0 +1,378 @@
+//! Minimal JSON Schema validator for plugin parameters
+//!
+//! Only the keywords plugins actually use are supported: `type`, `enum`,
+//! `minimum`, `maximum`, `exclusiveMinimum`, `properties`, `required`,
+//! `additionalProperties` and `items`. Unknown keywords are ignored, as the specification demands.
+//!
+//! Integral floats such as `2.0` are integers, as in the specification;
+//! [`normalize_integers`] rewrites them for plugins. A string property
+//! annotated with `"format": "path"` names a file; see [`resolve_paths`].
+
+use serde_json::Value;
+use std::path::Path;
+
+/// Validates `value` against `schema`, returning every violation found
+///
+/// Each message starts with the path of the offending field, e.g.
+/// `radius: expected integer, got string`.
+pub fn validate(schema: &Value, value: &Value) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    validate_at(schema, value, "", &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn describe(path: &str) -> &str {
+    if path.is_empty() { "params" } else { path }
+}
+
+fn field_path(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.to_owned()
+    } else {
+        format!("{path}.{field}")
+    }
+}
+
+/// JSON Schema type of `value`; as the specification says, a number with a
+/// zero fractional part such as `2.0` is an integer
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(n) if n.as_f64().is_some_and(|f| f.fract() == 0.0) => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|name| matches_type(name, value)) {
+            errors.push(format!(
+                "{}: expected {}, got {}",
+                describe(path),
+                allowed.join(" or "),
+                type_name(value)
+            ));
+            // Remaining keywords would only repeat the same mistake
+            return;
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum")
+        && !options.contains(value)
+    {
+        let options: Vec<String> = options.iter().map(Value::to_string).collect();
+        errors.push(format!(
+            "{}: expected one of {}, got {value}",
+            describe(path),
+            options.join(", ")
+        ));
+    }
+
+    if let Some(number) = value.as_f64() {
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64)
+            && number < minimum
+        {
+            errors.push(format!(
+                "{}: {value} is less than the minimum {minimum}",
+                describe(path)
+            ));
+        }
+        if let Some(minimum) = schema.get("exclusiveMinimum").and_then(Value::as_f64)
+            && number <= minimum
+        {
+            errors.push(format!(
+                "{}: {value} must be greater than {minimum}",
+                describe(path)
+            ));
+        }
+        if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64)
+            && number > maximum
+        {
+            errors.push(format!(
+                "{}: {value} is greater than the maximum {maximum}",
+                describe(path)
+            ));
+        }
+    }
+
+    if let Value::Object(object) = value {
+        let properties = schema.get("properties").and_then(Value::as_object);
+
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for field in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(field) {
+                    errors.push(format!(
+                        "{}: missing required field",
+                        field_path(path, field)
+                    ));
+                }
+            }
+        }
+
+        for (field, field_value) in object {
+            let nested = field_path(path, field);
+            match properties.and_then(|p| p.get(field)) {
+                Some(field_schema) => validate_at(field_schema, field_value, &nested, errors),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => {
+                        errors.push(format!("{nested}: unknown field"));
+                    }
+                    Some(extra @ Value::Object(_)) => {
+                        validate_at(extra, field_value, &nested, errors);
+                    }
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (index, item) in items.iter().enumerate() {
+            validate_at(item_schema, item, &format!("{path}[{index}]"), errors);
+        }
+    }
+}
+
+/// Makes every relative path in `value` relative to `base`
+///
+/// Paths are the strings whose schema has `"format": "path"`; everything
+/// else is left as is.
+pub fn resolve_paths(schema: &Value, value: &mut Value, base: &Path) {
+    visit(schema, value, &mut |schema, value| {
+        if schema.get("format").and_then(Value::as_str) == Some("path")
+            && let Value::String(path) = value
+            && Path::new(path.as_str()).is_relative()
+        {
+            *path = base.join(path.as_str()).to_string_lossy().into_owned();
+        }
+    });
+}
+
+/// Rewrites integral floats such as `2.0` in `integer` fields as integers
+///
+/// [`validate`] accepts them, but a plugin deserializing into an integer
+/// type would not.
+pub fn normalize_integers(schema: &Value, value: &mut Value) {
+    visit(schema, value, &mut |schema, value| {
+        let integer = match schema.get("type") {
+            Some(Value::String(name)) => name == "integer",
+            Some(Value::Array(names)) => names.iter().any(|name| name == "integer"),
+            _ => false,
+        };
+        if !integer || !value.is_f64() || type_name(value) != "integer" {
+            return;
+        }
+        let float = value.as_f64().unwrap_or_default();
+        if float >= 0.0 && float <= u64::MAX as f64 {
+            *value = Value::from(float as u64);
+        } else if float >= i64::MIN as f64 {
+            *value = Value::from(float as i64);
+        }
+    });
+}
+
+/// Calls `f` with every value in `value` and its schema, found through
+/// `properties` and `items`, parents before children
+fn visit(schema: &Value, value: &mut Value, f: &mut impl FnMut(&Value, &mut Value)) {
+    f(schema, value);
+    match value {
+        Value::Object(object) => {
+            let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+                return;
+            };
+            for (field, field_value) in object {
+                if let Some(field_schema) = properties.get(field) {
+                    visit(field_schema, field_value, f);
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for item in items {
+                    visit(item_schema, item, f);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn blur_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "radius": {"type": "integer", "minimum": 0},
+                "iterations": {"type": "integer", "minimum": 0},
+                "mode": {"type": "string", "enum": ["box", "gaussian"]}
+            },
+            "required": ["radius", "iterations"],
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn test_valid_params() {
+        let params = json!({"radius": 2, "iterations": 3, "mode": "box"});
+        assert_eq!(validate(&blur_schema(), &params), Ok(()));
+    }
+
+    #[test]
+    fn test_typo_reports_unknown_and_missing_field() {
+        let params = json!({"radus": 2, "iterations": 3});
+        assert_eq!(
+            validate(&blur_schema(), &params),
+            Err(vec![
+                "radius: missing required field".to_owned(),
+                "radus: unknown field".to_owned(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_wrong_type_and_range() {
+        let params = json!({"radius": "2", "iterations": -1, "mode": "motion"});
+        assert_eq!(
+            validate(&blur_schema(), &params),
+            Err(vec![
+                "iterations: -1 is less than the minimum 0".to_owned(),
+                r#"mode: expected one of "box", "gaussian", got "motion""#.to_owned(),
+                "radius: expected integer, got string".to_owned(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_integral_float_is_integer() {
+        let params = json!({"radius": 3.0, "iterations": 1});
+        assert_eq!(validate(&blur_schema(), &params), Ok(()));
+
+        let params = json!({"radius": 2.5, "iterations": 1});
+        assert_eq!(
+            validate(&blur_schema(), &params),
+            Err(vec!["radius: expected integer, got number".to_owned()])
+        );
+    }
+
+    #[test]
+    fn test_normalize_integers() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "radius": {"type": "integer"},
+                "offset": {"type": ["integer", "null"]},
+                "sigma": {"type": "number"},
+                "rects": {"type": "array", "items": {"type": "integer"}}
+            }
+        });
+        let mut params = json!({
+            "radius": 3.0,
+            "offset": -2.0,
+            "sigma": 2.0,
+            "rects": [1.0, 2]
+        });
+        normalize_integers(&schema, &mut params);
+        assert_eq!(
+            params.to_string(),
+            r#"{"offset":-2,"radius":3,"rects":[1,2],"sigma":2.0}"#
+        );
+    }
+
+    #[test]
+    fn test_exclusive_minimum() {
+        let schema = json!({"type": "number", "exclusiveMinimum": 0});
+        assert_eq!(validate(&schema, &json!(0.5)), Ok(()));
+        assert_eq!(
+            validate(&schema, &json!(0)),
+            Err(vec!["params: 0 must be greater than 0".to_owned()])
+        );
+    }
+
+    #[test]
+    fn test_nested_paths() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "rects": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {"x": {"type": "integer"}},
+                        "required": ["x"]
+                    }
+                }
+            }
+        });
+        let params = json!({"rects": [{"x": 1}, {"x": 1.5}, {}]});
+        assert_eq!(
+            validate(&schema, &params),
+            Err(vec![
+                "rects[1].x: expected integer, got number".to_owned(),
+                "rects[2].x: missing required field".to_owned(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_not_an_object() {
+        assert_eq!(
+            validate(&blur_schema(), &json!([1, 2])),
+            Err(vec!["params: expected object, got array".to_owned()])
+        );
+    }
+
+    #[test]
+    fn test_resolve_paths() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "mask": {"type": "string", "format": "path"},
+                "masks": {"type": "array", "items": {"type": "string", "format": "path"}},
+                "mode": {"type": "string"}
+            }
+        });
+        let mut params = json!({
+            "mask": "masks/face.png",
+            "masks": ["a.png", "/abs/b.png"],
+            "mode": "box"
+        });
+        resolve_paths(&schema, &mut params, Path::new("recipes"));
+        assert_eq!(
+            params,
+            json!({
+                "mask": Path::new("recipes").join("masks/face.png").to_str().unwrap(),
+                "masks": [Path::new("recipes").join("a.png").to_str().unwrap(), "/abs/b.png"],
+                "mode": "box"
+            })
+        );
+    }
+}
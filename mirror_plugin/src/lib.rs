@@ -1,123 +1,84 @@
 //! Mirror plugin for image processing application
 
+use plugin_sdk::{Context, ImagePlugin, ImageView, Result};
 use serde::Deserialize;
 use std::ffi::CStr;
-use std::os::raw::c_char;
 
-#[derive(Deserialize)]
+/// JSON Schema параметров плагина.
+const PARAMS_SCHEMA: &CStr = cr#"{
+    "type": "object",
+    "properties": {
+        "horizontal": {
+            "type": "boolean",
+            "description": "Отразить изображение слева направо"
+        },
+        "vertical": {
+            "type": "boolean",
+            "description": "Отразить изображение сверху вниз"
+        }
+    },
+    "required": ["horizontal", "vertical"],
+    "additionalProperties": false
+}"#;
+
+plugin_sdk::export_plugin! {
+    plugin: Mirror,
+    name: c"mirror_plugin",
+    description: c"Горизонтальное и вертикальное зеркалирование изображения",
+    params_schema: PARAMS_SCHEMA,
+}
+
+/// Параметры `{"horizontal": bool, "vertical": bool}`; без параметров
+/// изображение не меняется.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
 struct Params {
     horizontal: bool,
     vertical: bool,
 }
 
-/// Применяет горизонтальное и/или вертикальное зеркалирование к изображению на месте.
-///
-/// # Safety
-/// 
-/// Изображение должно быть в формате RGBA8 (4 байта на пиксель). Функция создаёт
-/// временную копию исходных данных и записывает результат обратно в тот же буфер.
-///
-/// # Параметры
-///
-/// - `width`, `height`: размеры изображения в пикселях (максимум 2 147 483 647)
-/// - `data`: указатель на буфер в формате RGBA8 (4 байта на пиксель)
-/// - `params`: JSON-строка с параметрами `{"horizontal": bool, "vertical": bool}`
-///   или null (используются значения по умолчанию: оба флага = false)
+/// Горизонтальное и/или вертикальное зеркалирование на месте.
 ///
-/// # Безопасность
-///
-/// Эта функция является FFI-границей. Вызывающая сторона ОБЯЗАНА гарантировать:
-///
-/// - `data` указывает на валидный, изменяемый буфер размером не менее
-///   `width × height × 4` байт в формате RGBA8;
-/// - буфер правильно выровнен и остаётся валидным на время выполнения;
-/// - `params` — либо null, либо корректная нуль-терминированная C-строка в UTF-8;
-/// - функция не вызывается конкурентно для одного и того же буфера.
-///
-/// # Возврат
-///
-/// - `0` — успешно обработано;
-/// - `-1` — ошибка (переполнение арифметики, слишком большой размер изображения,
-///   невалидная кодировка параметров, null-указатель при ненулевом буфере).
-#[unsafe(no_mangle)]
-pub unsafe extern "C" fn process_image(
-    width: u32,
-    height: u32,
-    data: *mut u8,
-    params: *const c_char,
-) -> i32 {
-    let total_pixels = match width.checked_mul(height) {
-        Some(v) => v,
-        None => return -1,
-    };
-
-    let buffer_size = match total_pixels.checked_mul(4) {
-        Some(v) => v,
-        None => return -1, // переполнение при умножении на 4 канала
-    };
-
-    let len: usize = match buffer_size.try_into() {
-        Ok(v) => v,
-        Err(_) => return -1, // буфер слишком велик для текущей архитектуры
-    };
-
-    if len > 0 && data.is_null() {
-        return -1;
-    }
+/// Плагин создаёт временную копию исходных данных и записывает результат
+/// обратно в тот же буфер.
+struct Mirror;
 
-    let w: usize = match width.try_into() {
-        Ok(v) => v,
-        Err(_) => return -1,
-    };
-    let h: usize = match height.try_into() {
-        Ok(v) => v,
-        Err(_) => return -1,
-    };
-
-    // Создание среза из сырых данных
-    let slice = unsafe { std::slice::from_raw_parts_mut(data, len) };
-    let params_str = if params.is_null() {
-        ""
-    } else {
-        match unsafe { CStr::from_ptr(params) }.to_str() {
-            Ok(s) => s,
-            Err(_) => return -1,
-        }
-    };
+impl ImagePlugin for Mirror {
+    type Params = Params;
 
-    let params: Params = match serde_json::from_str(params_str) {
-        Ok(p) => p,
-        Err(_) => Params {
-            horizontal: false,
-            vertical: false,
-        },
-    };
-
-    let copy = slice.to_vec();
-    for y in 0..h {
-        for x in 0..w {
-            let src_x = if params.horizontal {
-                w.saturating_sub(1).saturating_sub(x)
-            } else {
-                x
-            };
-            let src_y = if params.vertical {
-                h.saturating_sub(1).saturating_sub(y)
-            } else {
-                y
-            };
-            let dst = (y * w + x) * 4;
-            let src = (src_y * w + src_x) * 4;
-            slice[dst..dst + 4].copy_from_slice(&copy[src..src + 4]);
+    fn process(image: &mut ImageView<'_>, params: Params, context: &Context<'_>) -> Result<()> {
+        let (w, h) = image.dimensions();
+        let slice = image.as_bytes_mut();
+        let copy = slice.to_vec();
+        for y in 0..h {
+            context.checkpoint(y as f32 / h as f32, "зеркалирование")?;
+            for x in 0..w {
+                let src_x = if params.horizontal {
+                    w.saturating_sub(1).saturating_sub(x)
+                } else {
+                    x
+                };
+                let src_y = if params.vertical {
+                    h.saturating_sub(1).saturating_sub(y)
+                } else {
+                    y
+                };
+                let dst = (y * w + x) * 4;
+                let src = (src_y * w + src_x) * 4;
+                slice[dst..dst + 4].copy_from_slice(&copy[src..src + 4]);
+            }
         }
+        Ok(())
     }
-    0
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use plugin_sdk::abi::{ABI_VERSION, PIXEL_FORMAT_RGBA8};
+    use plugin_sdk::{ERROR_INVALID_PARAMS, ERROR_NULL_POINTER};
     use std::ffi::CString;
+    use std::os::raw::c_char;
 
     /// Вспомогательная функция для безопасного вызова FFI-функции из тестов
     unsafe fn call_process_image(
@@ -131,7 +92,15 @@ mod tests {
             None => std::ptr::null(),
         };
 
-        let result = unsafe { process_image(width, height, data.as_mut_ptr(), params_ptr) };
+        let result = unsafe {
+            process_image(
+                width,
+                height,
+                data.as_mut_ptr(),
+                params_ptr,
+                std::ptr::null(),
+            )
+        };
 
         // Освобождаем память CString, если она была создана
         if !params_ptr.is_null() {
@@ -237,8 +206,15 @@ mod tests {
         // Передаём максимальные значения — функция должна вернуть ошибку, а не паниковать
         let mut dummy = [0u8; 4];
 
-        let result =
-            unsafe { process_image(u32::MAX, u32::MAX, dummy.as_mut_ptr(), std::ptr::null()) };
+        let result = unsafe {
+            process_image(
+                u32::MAX,
+                u32::MAX,
+                dummy.as_mut_ptr(),
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        };
 
         assert_eq!(
             result, -1,
@@ -251,11 +227,96 @@ mod tests {
         // Пустое изображение (0×0) — корректный случай, не должен вызывать ошибок
         let mut dummy = Vec::<u8>::new();
 
-        let result = unsafe { process_image(0, 0, dummy.as_mut_ptr(), std::ptr::null()) };
+        let result =
+            unsafe { process_image(0, 0, dummy.as_mut_ptr(), std::ptr::null(), std::ptr::null()) };
 
         assert_eq!(
             result, 0,
             "Пустое изображение (0×0) должно обрабатываться без ошибок"
         );
     }
+
+    #[test]
+    fn test_descriptor() {
+        // Дескриптор должен сообщать текущую версию ABI и имя плагина
+        let descriptor = unsafe { &*plugin_descriptor() };
+
+        assert_eq!(descriptor.abi_version, ABI_VERSION);
+        assert_eq!(
+            descriptor.pixel_formats & PIXEL_FORMAT_RGBA8,
+            PIXEL_FORMAT_RGBA8
+        );
+        assert_eq!(
+            unsafe { CStr::from_ptr(descriptor.name) }.to_str(),
+            Ok("mirror_plugin")
+        );
+        assert_eq!(
+            unsafe { CStr::from_ptr(descriptor.version) }.to_str(),
+            Ok(env!("CARGO_PKG_VERSION"))
+        );
+    }
+
+    #[test]
+    fn test_params_schema() {
+        // Схема параметров должна быть корректным JSON и описывать все поля `Params`
+        let schema: serde_json::Value =
+            serde_json::from_str(PARAMS_SCHEMA.to_str().unwrap()).unwrap();
+
+        assert_eq!(schema["type"], "object");
+        assert!(schema["properties"]["horizontal"].is_object());
+        assert!(schema["properties"]["vertical"].is_object());
+    }
+
+    #[test]
+    fn test_invalid_params_reported() {
+        // Опечатка в параметрах — ошибка с кодом и текстом, а не тихие значения по умолчанию
+        let mut data = vec![255, 0, 0, 255];
+        let result = unsafe { call_process_image(1, 1, &mut data, Some(r#"{"radus": 2}"#)) };
+
+        assert_eq!(result, ERROR_INVALID_PARAMS);
+        let message = unsafe { CStr::from_ptr(plugin_last_error()) }
+            .to_str()
+            .unwrap();
+        assert!(message.contains("невалидные параметры"), "{message}");
+
+        // Успешный вызов сбрасывает сообщение
+        let result = unsafe {
+            call_process_image(
+                1,
+                1,
+                &mut data,
+                Some(r#"{"horizontal": true, "vertical": false}"#),
+            )
+        };
+        assert_eq!(result, 0);
+        assert!(unsafe { CStr::from_ptr(plugin_last_error()) }.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_field_rejected() {
+        // Лишнее поле рядом с обязательными не игнорируется молча
+        let mut data = vec![255, 0, 0, 255];
+        let params = r#"{"horizontal": true, "vertical": false, "horizontl": true}"#;
+        let result = unsafe { call_process_image(1, 1, &mut data, Some(params)) };
+
+        assert_eq!(result, ERROR_INVALID_PARAMS);
+        let message = unsafe { CStr::from_ptr(plugin_last_error()) }
+            .to_str()
+            .unwrap();
+        assert!(message.contains("horizontl"), "{message}");
+    }
+
+    #[test]
+    fn test_null_data_reported() {
+        let result = unsafe {
+            process_image(
+                2,
+                2,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(result, ERROR_NULL_POINTER);
+    }
 }
@@ -0,0 +1,154 @@
+//! Бинарный интерфейс между хостом и плагинами
+//!
+//! Эти определения разделяют хост и все плагины, поэтому любое изменение
+//! раскладки структур или смысла кодов требует увеличить [`ABI_VERSION`].
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+
+/// Версия ABI, которую реализуют плагины, собранные этим SDK.
+pub const ABI_VERSION: u32 = 4;
+
+/// Формат пикселей RGBA8 (4 байта на пиксель).
+pub const PIXEL_FORMAT_RGBA8: u32 = 1 << 0;
+
+/// Плагин экспортирует `process_image` и меняет изображение на месте.
+pub const CAPABILITY_IN_PLACE: u32 = 1 << 0;
+/// Плагин экспортирует `transform_image` и `free_image` и возвращает новое
+/// изображение, размеры которого могут отличаться от исходных.
+pub const CAPABILITY_TRANSFORM: u32 = 1 << 1;
+
+/// Размер изображения или буфера не помещается в допустимый диапазон.
+pub const ERROR_OVERFLOW: i32 = -1;
+/// Передан нулевой указатель на непустой буфер.
+pub const ERROR_NULL_POINTER: i32 = -2;
+/// Строка параметров не является корректным UTF-8.
+pub const ERROR_INVALID_UTF8: i32 = -3;
+/// Строка параметров не соответствует параметрам плагина.
+pub const ERROR_INVALID_PARAMS: i32 = -4;
+/// Код плагина запаниковал; паника перехвачена на FFI-границе.
+pub const ERROR_PANIC: i32 = -5;
+/// Хост запросил отмену через [`HostContext`], обработка прервана.
+pub const ERROR_CANCELLED: i32 = -6;
+
+/// Сигнатура `process_image`.
+pub type ProcessImageFn = unsafe extern "C" fn(
+    width: u32,
+    height: u32,
+    data: *mut u8,
+    params: *const c_char,
+    context: *const HostContext,
+) -> i32;
+
+/// Сигнатура `transform_image`.
+///
+/// Входной буфер только читается. При успехе плагин заполняет `output`
+/// буфером, который хост обязан вернуть через `free_image`; при ошибке
+/// `output` не изменяется и `data` остаётся null. Если плагин всё же оставил
+/// буфер, хост освобождает его через `free_image`.
+pub type TransformImageFn = unsafe extern "C" fn(
+    width: u32,
+    height: u32,
+    data: *const u8,
+    params: *const c_char,
+    context: *const HostContext,
+    output: *mut OutputImage,
+) -> i32;
+
+/// Сигнатура `free_image`: освобождает буфер из [`OutputImage`].
+pub type FreeImageFn = unsafe extern "C" fn(data: *mut u8, len: usize);
+
+/// Сигнатура `plugin_descriptor`.
+pub type PluginDescriptorFn = unsafe extern "C" fn() -> *const PluginDescriptor;
+
+/// Сигнатура `plugin_last_error`.
+pub type LastErrorFn = unsafe extern "C" fn() -> *const c_char;
+
+/// Обратные вызовы хоста на время одного вызова `process_image` или
+/// `transform_image`.
+///
+/// Указатель на контекст может быть null, как и любой из обратных вызовов:
+/// тогда прогресс не сообщается, а отмена не запрашивается. Плагин может
+/// вызывать их из любого своего потока, поэтому хост обязан делать их
+/// потокобезопасными.
+#[repr(C)]
+pub struct HostContext {
+    /// Данные хоста, передаются первым аргументом в каждый обратный вызов
+    pub user_data: *mut c_void,
+    /// Сообщает долю выполненной работы от 0 до 1 и название текущего этапа
+    /// (нуль-терминированная строка, валидна только на время вызова)
+    pub report_progress:
+        Option<unsafe extern "C" fn(user_data: *mut c_void, fraction: f32, stage: *const c_char)>,
+    /// Возвращает ненулевое значение, если хост просит прервать обработку;
+    /// плагин в таком случае возвращает [`ERROR_CANCELLED`]
+    pub is_cancelled: Option<unsafe extern "C" fn(user_data: *mut c_void) -> i32>,
+}
+
+/// Описание плагина, которое хост читает до первого вызова `process_image`.
+///
+/// Поле `abi_version` всегда идёт первым: по нему хост решает, можно ли
+/// доверять остальной части структуры.
+#[repr(C)]
+pub struct PluginDescriptor {
+    /// Версия ABI, под которую собран плагин
+    pub abi_version: u32,
+    /// Имя плагина (нуль-терминированная строка)
+    pub name: *const c_char,
+    /// Семантическая версия плагина (нуль-терминированная строка)
+    pub version: *const c_char,
+    /// Краткое описание плагина (нуль-терминированная строка)
+    pub description: *const c_char,
+    /// Битовая маска поддерживаемых форматов пикселей
+    pub pixel_formats: u32,
+    /// JSON Schema параметров (нуль-терминированная строка)
+    pub params_schema: *const c_char,
+    /// Битовая маска экспортируемых точек входа (`CAPABILITY_*`)
+    pub capabilities: u32,
+}
+
+/// Результат `transform_image`: новое изображение RGBA8 в памяти плагина.
+#[repr(C)]
+#[derive(Debug)]
+pub struct OutputImage {
+    /// Ширина в пикселях
+    pub width: u32,
+    /// Высота в пикселях
+    pub height: u32,
+    /// Пиксели построчно, `width × height × 4` байт
+    pub data: *mut u8,
+    /// Длина буфера в байтах; передаётся обратно в `free_image`
+    pub len: usize,
+}
+
+impl Default for OutputImage {
+    fn default() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            data: std::ptr::null_mut(),
+            len: 0,
+        }
+    }
+}
+
+// Указатели ссылаются только на статические строки, поэтому разделять
+// дескриптор между потоками безопасно.
+unsafe impl Sync for PluginDescriptor {}
+
+/// Превращает строку с завершающим нулём в `&CStr` на этапе компиляции.
+///
+/// Нужна макросу [`export_plugin!`](crate::export_plugin) для версии из
+/// `CARGO_PKG_VERSION`, у которой нет литерала `c"..."`.
+#[doc(hidden)]
+pub const fn c_str(with_nul: &'static str) -> &'static CStr {
+    match CStr::from_bytes_with_nul(with_nul.as_bytes()) {
+        Ok(s) => s,
+        Err(_) => panic!("строка должна заканчиваться единственным нулевым байтом"),
+    }
+}
+
+/// Указатель на строку для полей [`PluginDescriptor`].
+#[doc(hidden)]
+pub const fn c_str_ptr(s: &'static CStr) -> *const c_char {
+    s.as_ptr()
+}
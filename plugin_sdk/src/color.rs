@@ -0,0 +1,88 @@
+//! Преобразования между sRGB и линейным светом
+//!
+//! Байты RGB в [`ImageView`](crate::ImageView) закодированы в sRGB: значения
+//! пропорциональны не яркости, а её гамма-сжатой версии. Усреднять, смешивать
+//! и размывать физически корректно в линейном пространстве, поэтому цвет
+//! сначала раскодируется в `f32` от 0 до 1, обрабатывается и кодируется
+//! обратно. Альфа-канал в sRGB не кодируется и лишь масштабируется к 0..1.
+
+use std::sync::LazyLock;
+
+/// Линейные значения всех 256 байт sRGB.
+static DECODE: LazyLock<[f32; 256]> = LazyLock::new(|| {
+    std::array::from_fn(|value| {
+        let value = value as f32 / 255.0;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    })
+});
+
+/// Байт sRGB → линейная яркость от 0 до 1.
+pub fn srgb_to_linear(value: u8) -> f32 {
+    DECODE[usize::from(value)]
+}
+
+/// Линейная яркость → ближайший байт sRGB; значения вне 0..1 обрезаются.
+pub fn linear_to_srgb(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let encoded = if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
+
+/// Пиксели RGBA8 → RGBA `f32`: цвет в линейном свете, альфа от 0 до 1.
+pub(crate) fn decode(data: &[u8]) -> Vec<f32> {
+    data.chunks_exact(crate::CHANNELS)
+        .flat_map(|pixel| {
+            [
+                srgb_to_linear(pixel[0]),
+                srgb_to_linear(pixel[1]),
+                srgb_to_linear(pixel[2]),
+                f32::from(pixel[3]) / 255.0,
+            ]
+        })
+        .collect()
+}
+
+/// Обратное к [`decode`] преобразование в `data`.
+pub(crate) fn encode(linear: &[f32], data: &mut [u8]) {
+    for (pixel, out) in linear
+        .chunks_exact(crate::CHANNELS)
+        .zip(data.chunks_exact_mut(crate::CHANNELS))
+    {
+        for c in 0..3 {
+            out[c] = linear_to_srgb(pixel[c]);
+        }
+        out[3] = (pixel[3].clamp(0.0, 1.0) * 255.0).round() as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference_values() {
+        assert_eq!(srgb_to_linear(0), 0.0);
+        assert_eq!(srgb_to_linear(255), 1.0);
+        // Середина шкалы sRGB — около пятой части линейной яркости
+        assert!((srgb_to_linear(128) - 0.2158605).abs() < 1e-6);
+        // Половина линейной яркости кодируется как 188, а не 128
+        assert_eq!(linear_to_srgb(0.5), 188);
+        assert_eq!(linear_to_srgb(-1.0), 0);
+        assert_eq!(linear_to_srgb(2.0), 255);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for value in 0..=255 {
+            assert_eq!(linear_to_srgb(srgb_to_linear(value)), value);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Прогресс и отмена: безопасная обёртка над [`HostContext`]
+
+use std::ffi::CString;
+
+use crate::abi::HostContext;
+use crate::error::{PluginError, Result};
+
+/// Обратные вызовы хоста, доступные плагину во время обработки.
+///
+/// Контекст можно передавать в рабочие потоки плагина: ABI требует от хоста
+/// потокобезопасных обратных вызовов.
+#[derive(Clone, Copy, Default)]
+pub struct Context<'a> {
+    host: Option<&'a HostContext>,
+}
+
+// HostContext хранит сырые указатели, но по контракту ABI его обратные
+// вызовы можно вызывать из любого потока.
+unsafe impl Send for Context<'_> {}
+unsafe impl Sync for Context<'_> {}
+
+impl<'a> Context<'a> {
+    /// Контекст без хоста: прогресс никуда не сообщается, отмены не бывает.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Оборачивает указатель, полученный через FFI.
+    ///
+    /// # Safety
+    ///
+    /// `context` — либо null, либо указатель на [`HostContext`], валидный в
+    /// течение `'a`.
+    pub unsafe fn from_raw(context: *const HostContext) -> Self {
+        Self {
+            host: unsafe { context.as_ref() },
+        }
+    }
+
+    /// Сообщает хосту долю выполненной работы (от 0 до 1) и название этапа.
+    pub fn report(&self, fraction: f32, stage: &str) {
+        let Some(host) = self.host else { return };
+        let Some(report_progress) = host.report_progress else {
+            return;
+        };
+        let stage = CString::new(stage).unwrap_or_default();
+        unsafe { report_progress(host.user_data, fraction.clamp(0.0, 1.0), stage.as_ptr()) };
+    }
+
+    /// Просит ли хост прервать обработку.
+    pub fn is_cancelled(&self) -> bool {
+        let Some(host) = self.host else { return false };
+        let Some(is_cancelled) = host.is_cancelled else {
+            return false;
+        };
+        unsafe { is_cancelled(host.user_data) != 0 }
+    }
+
+    /// Сообщает прогресс и возвращает [`ERROR_CANCELLED`], если хост просит
+    /// остановиться.
+    ///
+    /// Удобно вызывать в цикле по строкам: `context.checkpoint(y as f32 / h, "…")?`.
+    pub fn checkpoint(&self, fraction: f32, stage: &str) -> Result<()> {
+        self.report(fraction, stage);
+        if self.is_cancelled() {
+            return Err(PluginError::cancelled());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abi::ERROR_CANCELLED;
+    use std::ffi::CStr;
+    use std::os::raw::{c_char, c_void};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder {
+        reports: Mutex<Vec<(f32, String)>>,
+        cancel: bool,
+    }
+
+    unsafe extern "C" fn record(user_data: *mut c_void, fraction: f32, stage: *const c_char) {
+        let recorder = unsafe { &*(user_data as *const Recorder) };
+        let stage = unsafe { CStr::from_ptr(stage) }
+            .to_str()
+            .unwrap()
+            .to_owned();
+        recorder.reports.lock().unwrap().push((fraction, stage));
+    }
+
+    unsafe extern "C" fn cancelled(user_data: *mut c_void) -> i32 {
+        let recorder = unsafe { &*(user_data as *const Recorder) };
+        i32::from(recorder.cancel)
+    }
+
+    fn host(recorder: &Recorder) -> HostContext {
+        HostContext {
+            user_data: recorder as *const Recorder as *mut c_void,
+            report_progress: Some(record),
+            is_cancelled: Some(cancelled),
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_reports_and_cancels() {
+        let recorder = Recorder::default();
+        let host = host(&recorder);
+        let context = unsafe { Context::from_raw(&host) };
+
+        assert_eq!(context.checkpoint(1.5, "проход 1/2"), Ok(()));
+        assert_eq!(
+            *recorder.reports.lock().unwrap(),
+            vec![(1.0, "проход 1/2".to_owned())]
+        );
+
+        let recorder = Recorder {
+            cancel: true,
+            ..Recorder::default()
+        };
+        let host = self::host(&recorder);
+        let context = unsafe { Context::from_raw(&host) };
+        assert_eq!(
+            context.checkpoint(0.5, "").unwrap_err().code(),
+            ERROR_CANCELLED
+        );
+    }
+
+    #[test]
+    fn test_null_context() {
+        let context = unsafe { Context::from_raw(std::ptr::null()) };
+        context.report(0.5, "этап");
+        assert!(!context.is_cancelled());
+        assert_eq!(Context::none().checkpoint(1.0, "этап"), Ok(()));
+    }
+}
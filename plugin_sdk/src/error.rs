@@ -0,0 +1,169 @@
+//! Ошибки плагинов и сообщение о последней ошибке
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::fmt;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::abi::{ERROR_CANCELLED, ERROR_INVALID_PARAMS, ERROR_OVERFLOW, ERROR_PANIC};
+
+/// Ошибка обработки: код возврата `process_image` и текст для
+/// `plugin_last_error`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginError {
+    code: i32,
+    message: String,
+}
+
+impl PluginError {
+    /// Ошибка с произвольным отрицательным кодом.
+    pub fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// Ошибка [`ERROR_INVALID_PARAMS`]: параметры разобраны, но недопустимы.
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(ERROR_INVALID_PARAMS, message)
+    }
+
+    /// Ошибка [`ERROR_OVERFLOW`]: размеры не помещаются в допустимый диапазон.
+    pub fn overflow(message: impl Into<String>) -> Self {
+        Self::new(ERROR_OVERFLOW, message)
+    }
+
+    /// Ошибка [`ERROR_CANCELLED`]: обработка прервана по запросу хоста.
+    pub fn cancelled() -> Self {
+        Self::new(ERROR_CANCELLED, "обработка отменена")
+    }
+
+    /// Код возврата `process_image`.
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+
+    /// Текст ошибки.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (код {})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+/// Результат обработки изображения плагином.
+pub type Result<T, E = PluginError> = std::result::Result<T, E>;
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Запоминает сообщение для `plugin_last_error` и возвращает код ошибки.
+pub fn fail(code: i32, message: impl Into<String>) -> i32 {
+    let message = CString::new(message.into())
+        .unwrap_or_else(|_| c"сообщение об ошибке содержит нулевой байт".into());
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+    code
+}
+
+/// Сбрасывает сообщение о последней ошибке в текущем потоке.
+pub fn clear_last_error() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::default());
+}
+
+/// Указатель на сообщение о последней ошибке в текущем потоке.
+///
+/// Строка остаётся валидной до следующего вызова [`fail`] или
+/// [`clear_last_error`] в этом же потоке.
+pub fn last_error_ptr() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "паника без сообщения"
+    }
+}
+
+/// Выполняет тело экспортируемой функции, не давая панике выйти за FFI-границу.
+///
+/// Раскрутка стека через `extern "C"` аварийно завершает весь процесс хоста,
+/// поэтому каждая экспортируемая точка входа должна оборачивать своё тело
+/// в `catch_panic`. Паника превращается в код [`ERROR_PANIC`] и сообщение,
+/// доступное через `plugin_last_error`.
+pub fn catch_panic(body: impl FnOnce() -> i32) -> i32 {
+    // Буферы после паники не используются: хост отбрасывает результат
+    // при ненулевом коде возврата, поэтому AssertUnwindSafe здесь уместен.
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(status) => status,
+        Err(payload) => fail(
+            ERROR_PANIC,
+            format!("паника в плагине: {}", panic_message(payload.as_ref())),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(last_error_ptr()) }
+            .to_str()
+            .unwrap()
+            .to_owned()
+    }
+
+    #[test]
+    fn test_catch_panic_passes_status_through() {
+        clear_last_error();
+        assert_eq!(catch_panic(|| 0), 0);
+        assert_eq!(
+            catch_panic(|| fail(ERROR_OVERFLOW, "слишком велико")),
+            ERROR_OVERFLOW
+        );
+        assert_eq!(last_error(), "слишком велико");
+    }
+
+    #[test]
+    fn test_catch_panic_converts_str_panic() {
+        let status = catch_panic(|| panic!("индекс за границей"));
+
+        assert_eq!(status, ERROR_PANIC);
+        assert_eq!(last_error(), "паника в плагине: индекс за границей");
+    }
+
+    #[test]
+    fn test_catch_panic_converts_formatted_panic() {
+        let data = [0u8; 4];
+        let index = data.len() + 1;
+        let status = catch_panic(|| i32::from(data[index]));
+
+        assert_eq!(status, ERROR_PANIC);
+        assert!(
+            last_error().contains("index out of bounds"),
+            "{}",
+            last_error()
+        );
+    }
+
+    #[test]
+    fn test_nul_in_message() {
+        fail(ERROR_INVALID_PARAMS, "a\0b");
+        assert_eq!(last_error(), "сообщение об ошибке содержит нулевой байт");
+    }
+}
@@ -0,0 +1,348 @@
+//! Безопасное представление буфера изображения
+
+use crate::abi::ERROR_NULL_POINTER;
+use crate::color;
+use crate::error::{PluginError, Result};
+
+/// Число байт на пиксель RGBA8.
+pub const CHANNELS: usize = 4;
+
+/// Изменяемое изображение RGBA8, переданное хостом.
+///
+/// Пиксели хранятся построчно без выравнивания строк, по [`CHANNELS`]
+/// байта на пиксель.
+#[derive(Debug)]
+pub struct ImageView<'a> {
+    width: u32,
+    height: u32,
+    data: &'a mut [u8],
+}
+
+/// Длина буфера `width × height × 4` с проверкой переполнения.
+fn buffer_len(width: u32, height: u32) -> Result<usize> {
+    usize::try_from(width)
+        .ok()
+        .zip(usize::try_from(height).ok())
+        .and_then(|(w, h)| w.checked_mul(h))
+        .and_then(|pixels| pixels.checked_mul(CHANNELS))
+        // Срез не может быть длиннее isize::MAX байт
+        .filter(|&len| isize::try_from(len).is_ok())
+        .ok_or_else(|| {
+            PluginError::overflow(format!("изображение {width}×{height} слишком велико"))
+        })
+}
+
+/// Проверяет, что буфер длиной `len` вмещает ровно `width × height` пикселей.
+fn check_len(width: u32, height: u32, len: usize) -> Result<()> {
+    if buffer_len(width, height)? != len {
+        return Err(PluginError::overflow(format!(
+            "буфер {len} байт не соответствует изображению {width}×{height}"
+        )));
+    }
+    Ok(())
+}
+
+fn pixel_offset(width: u32, height: u32, x: u32, y: u32) -> usize {
+    assert!(
+        x < width && y < height,
+        "пиксель ({x}, {y}) вне изображения {width}×{height}"
+    );
+    (y as usize * width as usize + x as usize) * CHANNELS
+}
+
+fn read_pixel(data: &[u8], width: u32, height: u32, x: u32, y: u32) -> [u8; CHANNELS] {
+    let offset = pixel_offset(width, height, x, y);
+    let mut pixel = [0; CHANNELS];
+    pixel.copy_from_slice(&data[offset..offset + CHANNELS]);
+    pixel
+}
+
+impl<'a> ImageView<'a> {
+    /// Оборачивает срез, длина которого должна быть ровно `width × height × 4`.
+    pub fn new(width: u32, height: u32, data: &'a mut [u8]) -> Result<Self> {
+        check_len(width, height, data.len())?;
+        Ok(Self {
+            width,
+            height,
+            data,
+        })
+    }
+
+    /// Оборачивает сырой указатель, полученный через FFI.
+    ///
+    /// Размер проверяется на переполнение, нулевой указатель допустим только
+    /// для пустого изображения.
+    ///
+    /// # Safety
+    ///
+    /// Если изображение непустое, `data` должен указывать на изменяемый буфер
+    /// не короче `width × height × 4` байт, который никто другой не читает и не
+    /// изменяет в течение `'a`.
+    pub unsafe fn from_raw(width: u32, height: u32, data: *mut u8) -> Result<Self> {
+        let len = buffer_len(width, height)?;
+        let data = if len == 0 {
+            // from_raw_parts_mut не принимает null даже для пустого среза
+            &mut []
+        } else if data.is_null() {
+            return Err(PluginError::new(
+                ERROR_NULL_POINTER,
+                "указатель на данные изображения равен null",
+            ));
+        } else {
+            unsafe { std::slice::from_raw_parts_mut(data, len) }
+        };
+        Ok(Self {
+            width,
+            height,
+            data,
+        })
+    }
+
+    /// Ширина в пикселях.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Высота в пикселях.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Ширина и высота в пикселях как `usize`.
+    pub fn dimensions(&self) -> (usize, usize) {
+        // Оба значения проверены в buffer_len
+        (self.width as usize, self.height as usize)
+    }
+
+    /// Все пиксели построчно.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.data
+    }
+
+    /// Все пиксели построчно, с возможностью изменения.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        self.data
+    }
+
+    /// Значение пикселя `(x, y)`.
+    ///
+    /// # Panics
+    ///
+    /// Если координаты выходят за границы изображения.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; CHANNELS] {
+        read_pixel(self.data, self.width, self.height, x, y)
+    }
+
+    /// Пиксели в линейном свете, см. [`color`].
+    pub fn to_linear(&self) -> Vec<f32> {
+        color::decode(self.data)
+    }
+
+    /// Записывает пиксели из линейного света, обратно к [`ImageView::to_linear`].
+    ///
+    /// # Panics
+    ///
+    /// Если длина `linear` не равна `width × height × 4`.
+    pub fn store_linear(&mut self, linear: &[f32]) {
+        assert_eq!(
+            linear.len(),
+            self.data.len(),
+            "буфер не соответствует изображению {}×{}",
+            self.width,
+            self.height
+        );
+        color::encode(linear, self.data);
+    }
+
+    /// Записывает пиксель `(x, y)`.
+    ///
+    /// # Panics
+    ///
+    /// Если координаты выходят за границы изображения.
+    pub fn set_pixel(&mut self, x: u32, y: u32, pixel: [u8; CHANNELS]) {
+        let offset = pixel_offset(self.width, self.height, x, y);
+        self.data[offset..offset + CHANNELS].copy_from_slice(&pixel);
+    }
+}
+
+/// Неизменяемое изображение RGBA8: вход `transform_image`.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageRef<'a> {
+    width: u32,
+    height: u32,
+    data: &'a [u8],
+}
+
+impl<'a> ImageRef<'a> {
+    /// Оборачивает сырой указатель, полученный через FFI.
+    ///
+    /// Проверки те же, что у [`ImageView::from_raw`].
+    ///
+    /// # Safety
+    ///
+    /// Если изображение непустое, `data` должен указывать на буфер не короче
+    /// `width × height × 4` байт, который никто не изменяет в течение `'a`.
+    pub unsafe fn from_raw(width: u32, height: u32, data: *const u8) -> Result<Self> {
+        let len = buffer_len(width, height)?;
+        let data = if len == 0 {
+            &[]
+        } else if data.is_null() {
+            return Err(PluginError::new(
+                ERROR_NULL_POINTER,
+                "указатель на данные изображения равен null",
+            ));
+        } else {
+            unsafe { std::slice::from_raw_parts(data, len) }
+        };
+        Ok(Self {
+            width,
+            height,
+            data,
+        })
+    }
+
+    /// Ширина в пикселях.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Высота в пикселях.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Ширина и высота в пикселях как `usize`.
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width as usize, self.height as usize)
+    }
+
+    /// Все пиксели построчно.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Значение пикселя `(x, y)`.
+    ///
+    /// # Panics
+    ///
+    /// Если координаты выходят за границы изображения.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; CHANNELS] {
+        read_pixel(self.data, self.width, self.height, x, y)
+    }
+
+    /// Пиксели в линейном свете, см. [`color`].
+    pub fn to_linear(&self) -> Vec<f32> {
+        color::decode(self.data)
+    }
+}
+
+/// Изображение RGBA8, которым владеет плагин: результат `transform_image`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl Image {
+    /// Прозрачное чёрное изображение заданного размера.
+    pub fn new(width: u32, height: u32) -> Result<Self> {
+        let len = buffer_len(width, height)?;
+        Ok(Self {
+            width,
+            height,
+            data: vec![0; len],
+        })
+    }
+
+    /// Изображение из готового буфера длиной ровно `width × height × 4`.
+    pub fn from_vec(width: u32, height: u32, data: Vec<u8>) -> Result<Self> {
+        check_len(width, height, data.len())?;
+        Ok(Self {
+            width,
+            height,
+            data,
+        })
+    }
+
+    /// Ширина в пикселях.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Высота в пикселях.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Изменяемое представление изображения.
+    pub fn view_mut(&mut self) -> ImageView<'_> {
+        ImageView {
+            width: self.width,
+            height: self.height,
+            data: &mut self.data,
+        }
+    }
+
+    /// Неизменяемое представление изображения.
+    pub fn as_image_ref(&self) -> ImageRef<'_> {
+        ImageRef {
+            width: self.width,
+            height: self.height,
+            data: &self.data,
+        }
+    }
+
+    /// Размеры и буфер пикселей.
+    pub fn into_raw(self) -> (u32, u32, Vec<u8>) {
+        (self.width, self.height, self.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abi::ERROR_OVERFLOW;
+
+    #[test]
+    fn test_from_raw_checks() {
+        let mut data = [0u8; 4];
+        let err =
+            unsafe { ImageView::from_raw(u32::MAX, u32::MAX, data.as_mut_ptr()) }.unwrap_err();
+        assert_eq!(err.code(), ERROR_OVERFLOW);
+
+        let err = unsafe { ImageView::from_raw(1, 1, std::ptr::null_mut()) }.unwrap_err();
+        assert_eq!(err.code(), ERROR_NULL_POINTER);
+
+        let view = unsafe { ImageView::from_raw(0, 0, std::ptr::null_mut()) }.unwrap();
+        assert!(view.as_bytes().is_empty());
+    }
+
+    #[test]
+    fn test_pixel_access() {
+        let mut data = vec![0u8; 2 * 2 * 4];
+        let mut view = ImageView::new(2, 2, &mut data).unwrap();
+        view.set_pixel(1, 0, [1, 2, 3, 4]);
+
+        assert_eq!(view.pixel(1, 0), [1, 2, 3, 4]);
+        assert_eq!(data[4..8], [1, 2, 3, 4]);
+        assert!(ImageView::new(3, 1, &mut data).is_err());
+    }
+
+    #[test]
+    fn test_linear_round_trip() {
+        let mut data = vec![0, 128, 255, 64, 188, 1, 2, 255];
+        let mut view = ImageView::new(2, 1, &mut data).unwrap();
+        let mut linear = view.to_linear();
+        assert_eq!(linear[2], 1.0);
+        assert_eq!(linear[3], 64.0 / 255.0);
+
+        view.store_linear(&linear);
+        assert_eq!(view.as_bytes(), [0, 128, 255, 64, 188, 1, 2, 255]);
+
+        // Среднее чёрного и белого в линейном свете — 188, а не 128
+        linear[0] = 0.5;
+        view.store_linear(&linear);
+        assert_eq!(view.pixel(0, 0), [188, 128, 255, 64]);
+    }
+}
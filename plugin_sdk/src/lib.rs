@@ -0,0 +1,580 @@
+//! SDK для плагинов обработки изображений
+//!
+//! Плагин реализует безопасный трейт [`ImagePlugin`] (обработка на месте)
+//! или [`TransformPlugin`] (новое изображение, возможно другого размера) и
+//! вызывает [`export_plugin!`], который генерирует все `extern "C"` точки
+//! входа: `plugin_descriptor`, `plugin_last_error` и `process_image` либо
+//! `transform_image` с `free_image`. Проверка
+//! размеров и указателей, разбор параметров и перехват паник выполняются
+//! в SDK, поэтому в коде плагина не остаётся `unsafe`.
+//!
+//! ```ignore
+//! #[derive(Default, serde::Deserialize)]
+//! struct Params {
+//!     invert: bool,
+//! }
+//!
+//! struct Invert;
+//!
+//! impl plugin_sdk::ImagePlugin for Invert {
+//!     type Params = Params;
+//!
+//!     fn process(
+//!         image: &mut plugin_sdk::ImageView<'_>,
+//!         params: Params,
+//!         context: &plugin_sdk::Context<'_>,
+//!     ) -> plugin_sdk::Result<()> {
+//!         if params.invert {
+//!             let height = image.height() as usize;
+//!             let row_len = image.width() as usize * plugin_sdk::CHANNELS;
+//!             for (y, row) in image.as_bytes_mut().chunks_mut(row_len).enumerate() {
+//!                 context.checkpoint(y as f32 / height as f32, "инверсия")?;
+//!                 row.iter_mut().for_each(|b| *b = 255 - *b);
+//!             }
+//!         }
+//!         Ok(())
+//!     }
+//! }
+//!
+//! plugin_sdk::export_plugin! {
+//!     plugin: Invert,
+//!     name: c"invert_plugin",
+//!     description: c"Инверсия цветов",
+//!     params_schema: c"{}",
+//! }
+//! ```
+
+#![warn(missing_docs)]
+
+pub mod abi;
+pub mod color;
+mod context;
+mod error;
+mod image;
+
+use serde::de::DeserializeOwned;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+use abi::{HostContext, OutputImage};
+
+pub use abi::{
+    ERROR_CANCELLED, ERROR_INVALID_PARAMS, ERROR_INVALID_UTF8, ERROR_NULL_POINTER, ERROR_OVERFLOW,
+    ERROR_PANIC,
+};
+pub use context::Context;
+pub use error::{PluginError, Result, catch_panic, clear_last_error, fail, last_error_ptr};
+pub use image::{CHANNELS, Image, ImageRef, ImageView};
+
+/// Безопасная часть плагина, которую оборачивает [`export_plugin!`].
+pub trait ImagePlugin {
+    /// Параметры из JSON-строки хоста.
+    ///
+    /// Если хост передал null или пустую строку, используется `Default`.
+    type Params: DeserializeOwned + Default;
+
+    /// Обрабатывает изображение на месте.
+    ///
+    /// При ошибке хост отбрасывает содержимое буфера, поэтому частично
+    /// обработанное изображение оставлять можно. Через `context` плагин
+    /// сообщает о прогрессе и узнаёт об отмене (см. [`Context::checkpoint`]).
+    fn process(
+        image: &mut ImageView<'_>,
+        params: Self::Params,
+        context: &Context<'_>,
+    ) -> Result<()>;
+}
+
+/// Безопасная часть плагина, меняющего размеры изображения (обрезка,
+/// масштабирование, поворот), которую оборачивает [`export_plugin!`].
+pub trait TransformPlugin {
+    /// Параметры из JSON-строки хоста.
+    ///
+    /// Если хост передал null или пустую строку, используется `Default`.
+    type Params: DeserializeOwned + Default;
+
+    /// Строит новое изображение по исходному.
+    fn transform(image: ImageRef<'_>, params: Self::Params, context: &Context<'_>)
+    -> Result<Image>;
+}
+
+/// Разбирает параметры из C-строки; null и пустая строка дают значения по умолчанию.
+///
+/// # Safety
+///
+/// `params` — либо null, либо корректная нуль-терминированная C-строка.
+pub unsafe fn parse_params<P: DeserializeOwned + Default>(params: *const c_char) -> Result<P> {
+    if params.is_null() {
+        return Ok(P::default());
+    }
+    let params = unsafe { CStr::from_ptr(params) }
+        .to_str()
+        .map_err(|e| PluginError::new(ERROR_INVALID_UTF8, format!("параметры не в UTF-8: {e}")))?;
+    if params.is_empty() {
+        return Ok(P::default());
+    }
+    serde_json::from_str(params)
+        .map_err(|e| PluginError::invalid_params(format!("невалидные параметры: {e}")))
+}
+
+/// Тело `process_image`, которое генерирует [`export_plugin!`].
+///
+/// # Safety
+///
+/// Те же требования, что у `process_image`: см. [`ImageView::from_raw`],
+/// [`parse_params`] и [`Context::from_raw`].
+#[doc(hidden)]
+pub unsafe fn process_image<P: ImagePlugin>(
+    width: u32,
+    height: u32,
+    data: *mut u8,
+    params: *const c_char,
+    context: *const HostContext,
+) -> i32 {
+    catch_panic(|| {
+        clear_last_error();
+        let context = unsafe { Context::from_raw(context) };
+        let result = unsafe { ImageView::from_raw(width, height, data) }.and_then(|mut image| {
+            let params = unsafe { parse_params::<P::Params>(params) }?;
+            P::process(&mut image, params, &context)
+        });
+        match result {
+            Ok(()) => 0,
+            Err(err) => fail(err.code(), err.message()),
+        }
+    })
+}
+
+/// Тело `transform_image`, которое генерирует [`export_plugin!`].
+///
+/// # Safety
+///
+/// Те же требования, что у `transform_image`: см. [`ImageRef::from_raw`],
+/// [`parse_params`] и [`Context::from_raw`]; `output` указывает на доступную
+/// для записи структуру.
+#[doc(hidden)]
+pub unsafe fn transform_image<P: TransformPlugin>(
+    width: u32,
+    height: u32,
+    data: *const u8,
+    params: *const c_char,
+    context: *const HostContext,
+    output: *mut OutputImage,
+) -> i32 {
+    catch_panic(|| {
+        clear_last_error();
+        if output.is_null() {
+            return fail(ERROR_NULL_POINTER, "указатель на результат равен null");
+        }
+        let context = unsafe { Context::from_raw(context) };
+        let result = unsafe { ImageRef::from_raw(width, height, data) }.and_then(|image| {
+            let params = unsafe { parse_params::<P::Params>(params) }?;
+            P::transform(image, params, &context)
+        });
+        match result {
+            Ok(image) => {
+                let (width, height, pixels) = image.into_raw();
+                let len = pixels.len();
+                let data = Box::into_raw(pixels.into_boxed_slice()).cast::<u8>();
+                unsafe {
+                    output.write(OutputImage {
+                        width,
+                        height,
+                        data,
+                        len,
+                    })
+                };
+                0
+            }
+            Err(err) => fail(err.code(), err.message()),
+        }
+    })
+}
+
+/// Тело `free_image`, которое генерирует [`export_plugin!`].
+///
+/// # Safety
+///
+/// `data` и `len` получены из [`OutputImage`], заполненного
+/// [`transform_image`], и освобождаются один раз; null игнорируется.
+#[doc(hidden)]
+pub unsafe fn free_image(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(data, len)) });
+    }
+}
+
+/// Экспортирует точки входа плагина.
+///
+/// С `plugin:` тип реализует [`ImagePlugin`] и экспортируется
+/// `process_image`; с `transform:` тип реализует [`TransformPlugin`] и
+/// экспортируются `transform_image` и `free_image`. `plugin_descriptor` и
+/// `plugin_last_error` экспортируются всегда.
+///
+/// `name`, `description` и `params_schema` — выражения типа `&'static CStr`;
+/// версия плагина берётся из `CARGO_PKG_VERSION` собираемого крейта.
+#[macro_export]
+macro_rules! export_plugin {
+    (
+        plugin: $plugin:ty,
+        name: $name:expr,
+        description: $description:expr,
+        params_schema: $params_schema:expr $(,)?
+    ) => {
+        $crate::export_plugin!(
+            @common $crate::abi::CAPABILITY_IN_PLACE, $name, $description, $params_schema
+        );
+
+        /// Обрабатывает изображение RGBA8 на месте.
+        ///
+        /// # Safety
+        ///
+        /// - `data` указывает на изменяемый буфер не короче `width × height × 4`
+        ///   байт (может быть null только для пустого изображения);
+        /// - `params` — либо null, либо нуль-терминированная C-строка;
+        /// - `context` — либо null, либо контекст хоста, валидный на время вызова;
+        /// - функция не вызывается конкурентно для одного и того же буфера.
+        ///
+        /// Возвращает `0` или отрицательный код ошибки из [`plugin_sdk::abi`];
+        /// текст ошибки доступен через `plugin_last_error`.
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn process_image(
+            width: u32,
+            height: u32,
+            data: *mut u8,
+            params: *const ::std::os::raw::c_char,
+            context: *const $crate::abi::HostContext,
+        ) -> i32 {
+            unsafe { $crate::process_image::<$plugin>(width, height, data, params, context) }
+        }
+    };
+    (
+        transform: $plugin:ty,
+        name: $name:expr,
+        description: $description:expr,
+        params_schema: $params_schema:expr $(,)?
+    ) => {
+        $crate::export_plugin!(
+            @common $crate::abi::CAPABILITY_TRANSFORM, $name, $description, $params_schema
+        );
+
+        /// Строит новое изображение RGBA8 по исходному.
+        ///
+        /// # Safety
+        ///
+        /// - `data` указывает на буфер не короче `width × height × 4` байт
+        ///   (может быть null только для пустого изображения), который
+        ///   функция только читает;
+        /// - `params` — либо null, либо нуль-терминированная C-строка;
+        /// - `context` — либо null, либо контекст хоста, валидный на время вызова;
+        /// - `output` указывает на доступную для записи структуру.
+        ///
+        /// При успехе возвращает `0` и заполняет `output`; буфер результата
+        /// нужно освободить через `free_image`. Иначе возвращает отрицательный
+        /// код ошибки из [`plugin_sdk::abi`], а `output` не изменяется.
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn transform_image(
+            width: u32,
+            height: u32,
+            data: *const u8,
+            params: *const ::std::os::raw::c_char,
+            context: *const $crate::abi::HostContext,
+            output: *mut $crate::abi::OutputImage,
+        ) -> i32 {
+            unsafe {
+                $crate::transform_image::<$plugin>(width, height, data, params, context, output)
+            }
+        }
+
+        /// Освобождает буфер, возвращённый `transform_image`.
+        ///
+        /// # Safety
+        ///
+        /// `data` и `len` взяты из одного `OutputImage` и освобождаются один раз.
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn free_image(data: *mut u8, len: usize) {
+            unsafe { $crate::free_image(data, len) }
+        }
+    };
+    (@common $capabilities:expr, $name:expr, $description:expr, $params_schema:expr) => {
+        static __PLUGIN_DESCRIPTOR: $crate::abi::PluginDescriptor = $crate::abi::PluginDescriptor {
+            abi_version: $crate::abi::ABI_VERSION,
+            name: $crate::abi::c_str_ptr($name),
+            version: $crate::abi::c_str(concat!(env!("CARGO_PKG_VERSION"), "\0")).as_ptr(),
+            description: $crate::abi::c_str_ptr($description),
+            pixel_formats: $crate::abi::PIXEL_FORMAT_RGBA8,
+            params_schema: $crate::abi::c_str_ptr($params_schema),
+            capabilities: $capabilities,
+        };
+
+        /// Возвращает указатель на статический дескриптор плагина.
+        ///
+        /// Указатель валиден всё время, пока библиотека загружена.
+        #[unsafe(no_mangle)]
+        pub extern "C" fn plugin_descriptor() -> *const $crate::abi::PluginDescriptor {
+            &__PLUGIN_DESCRIPTOR
+        }
+
+        /// Возвращает сообщение о последней ошибке обработки в текущем потоке.
+        ///
+        /// Строка остаётся валидной до следующего вызова `process_image` или
+        /// `transform_image` в этом же потоке. После успешного вызова возвращается пустая строка.
+        #[unsafe(no_mangle)]
+        pub extern "C" fn plugin_last_error() -> *const ::std::os::raw::c_char {
+            $crate::last_error_ptr()
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::ffi::CStr;
+
+    #[derive(Default, Deserialize)]
+    struct Params {
+        value: u8,
+    }
+
+    struct Fill;
+
+    impl ImagePlugin for Fill {
+        type Params = Params;
+
+        fn process(image: &mut ImageView<'_>, params: Params, _: &Context<'_>) -> Result<()> {
+            if params.value == 13 {
+                return Err(PluginError::invalid_params("13 не подходит"));
+            }
+            let pixels = image.as_bytes_mut();
+            if params.value == 99 {
+                // Ошибка программиста в плагине: паника не должна пересечь FFI
+                pixels[pixels.len()] = params.value;
+            }
+            pixels.fill(params.value);
+            Ok(())
+        }
+    }
+
+    export_plugin! {
+        plugin: Fill,
+        name: c"fill_plugin",
+        description: c"Заливка",
+        params_schema: c"{}",
+    }
+
+    #[derive(Default, Deserialize)]
+    struct CropParams {
+        width: u32,
+    }
+
+    /// Оставляет левые `width` столбцов изображения.
+    struct CropLeft;
+
+    impl TransformPlugin for CropLeft {
+        type Params = CropParams;
+
+        fn transform(image: ImageRef<'_>, params: CropParams, _: &Context<'_>) -> Result<Image> {
+            if params.width > image.width() {
+                return Err(PluginError::invalid_params("слишком широко"));
+            }
+            if params.width == 0 {
+                panic!("пустой результат не поддерживается");
+            }
+            let mut output = Image::new(params.width, image.height())?;
+            let mut view = output.view_mut();
+            for y in 0..image.height() {
+                for x in 0..params.width {
+                    view.set_pixel(x, y, image.pixel(x, y));
+                }
+            }
+            Ok(output)
+        }
+    }
+
+    fn last_error() -> &'static str {
+        unsafe { CStr::from_ptr(plugin_last_error()) }
+            .to_str()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_exported_process_image() {
+        let mut data = [1u8; 8];
+        let status = unsafe {
+            process_image(
+                2,
+                1,
+                data.as_mut_ptr(),
+                c"{\"value\": 7}".as_ptr(),
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(status, 0);
+        assert_eq!(data, [7; 8]);
+
+        // Без параметров используется Default
+        let status =
+            unsafe { process_image(2, 1, data.as_mut_ptr(), std::ptr::null(), std::ptr::null()) };
+        assert_eq!(status, 0);
+        assert_eq!(data, [0; 8]);
+    }
+
+    #[test]
+    fn test_exported_errors() {
+        let mut data = [0u8; 4];
+        let status = unsafe {
+            process_image(
+                1,
+                1,
+                data.as_mut_ptr(),
+                c"{\"value\": 13}".as_ptr(),
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(status, ERROR_INVALID_PARAMS);
+        assert_eq!(last_error(), "13 не подходит");
+
+        let status =
+            unsafe { process_image(1, 1, data.as_mut_ptr(), c"{".as_ptr(), std::ptr::null()) };
+        assert_eq!(status, ERROR_INVALID_PARAMS);
+        assert!(last_error().starts_with("невалидные параметры"));
+
+        let status = unsafe {
+            process_image(
+                1,
+                1,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(status, ERROR_NULL_POINTER);
+    }
+
+    #[test]
+    fn test_exported_panic_reported() {
+        // Паника внутри плагина возвращается хосту кодом и текстом
+        let mut data = [0u8; 4];
+        let status = unsafe {
+            process_image(
+                1,
+                1,
+                data.as_mut_ptr(),
+                c"{\"value\": 99}".as_ptr(),
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(status, ERROR_PANIC);
+        assert!(
+            last_error().starts_with("паника в плагине: index out of bounds"),
+            "{}",
+            last_error()
+        );
+
+        // Следующий успешный вызов сбрасывает сообщение
+        let status = unsafe {
+            process_image(
+                1,
+                1,
+                data.as_mut_ptr(),
+                c"{\"value\": 1}".as_ptr(),
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(status, 0);
+        assert_eq!(last_error(), "");
+    }
+
+    #[test]
+    fn test_exported_descriptor() {
+        let descriptor = unsafe { &*plugin_descriptor() };
+        assert_eq!(descriptor.abi_version, abi::ABI_VERSION);
+        assert_eq!(descriptor.capabilities, abi::CAPABILITY_IN_PLACE);
+        assert_eq!(
+            unsafe { CStr::from_ptr(descriptor.name) }.to_str(),
+            Ok("fill_plugin")
+        );
+        assert_eq!(
+            unsafe { CStr::from_ptr(descriptor.version) }.to_str(),
+            Ok(env!("CARGO_PKG_VERSION"))
+        );
+    }
+
+    #[test]
+    fn test_transform_image_returns_new_size() {
+        let data: Vec<u8> = (0..2 * 3 * 4).collect();
+        let mut output = OutputImage::default();
+        let status = unsafe {
+            transform_image::<CropLeft>(
+                3,
+                2,
+                data.as_ptr(),
+                c"{\"width\": 1}".as_ptr(),
+                std::ptr::null(),
+                &mut output,
+            )
+        };
+
+        assert_eq!(status, 0);
+        assert_eq!((output.width, output.height, output.len), (1, 2, 8));
+        let pixels = unsafe { std::slice::from_raw_parts(output.data, output.len) };
+        assert_eq!(pixels, [0, 1, 2, 3, 12, 13, 14, 15]);
+        unsafe { free_image(output.data, output.len) };
+    }
+
+    #[test]
+    fn test_transform_image_error_leaves_output() {
+        let data = [0u8; 4];
+        let mut output = OutputImage::default();
+        let status = unsafe {
+            transform_image::<CropLeft>(
+                1,
+                1,
+                data.as_ptr(),
+                c"{\"width\": 2}".as_ptr(),
+                std::ptr::null(),
+                &mut output,
+            )
+        };
+
+        assert_eq!(status, ERROR_INVALID_PARAMS);
+        assert_eq!(last_error(), "слишком широко");
+        assert!(output.data.is_null());
+
+        let status = unsafe {
+            transform_image::<CropLeft>(
+                1,
+                1,
+                data.as_ptr(),
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null_mut(),
+            )
+        };
+        assert_eq!(status, ERROR_NULL_POINTER);
+    }
+
+    #[test]
+    fn test_transform_image_panic_reported() {
+        let data = [0u8; 4];
+        let mut output = OutputImage::default();
+        let status = unsafe {
+            transform_image::<CropLeft>(
+                1,
+                1,
+                data.as_ptr(),
+                c"{\"width\": 0}".as_ptr(),
+                std::ptr::null(),
+                &mut output,
+            )
+        };
+
+        assert_eq!(status, ERROR_PANIC);
+        assert_eq!(
+            last_error(),
+            "паника в плагине: пустой результат не поддерживается"
+        );
+        assert!(output.data.is_null());
+    }
+}
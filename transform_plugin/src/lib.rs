@@ -0,0 +1,326 @@
+//! Transform plugin for image processing application
+
+#![warn(missing_docs)]
+
+use plugin_sdk::{Context, Image, ImageRef, PluginError, Result, TransformPlugin};
+use serde::Deserialize;
+use std::ffi::CStr;
+
+/// JSON Schema параметров плагина.
+const PARAMS_SCHEMA: &CStr = cr#"{
+    "type": "object",
+    "properties": {
+        "operation": {
+            "type": "string",
+            "enum": ["crop", "resize", "rotate"],
+            "description": "Операция: обрезка, масштабирование или поворот"
+        },
+        "x": {
+            "type": "integer",
+            "minimum": 0,
+            "description": "crop: левая граница области в пикселях"
+        },
+        "y": {
+            "type": "integer",
+            "minimum": 0,
+            "description": "crop: верхняя граница области в пикселях"
+        },
+        "width": {
+            "type": "integer",
+            "minimum": 1,
+            "description": "crop, resize: ширина результата в пикселях"
+        },
+        "height": {
+            "type": "integer",
+            "minimum": 1,
+            "description": "crop, resize: высота результата в пикселях"
+        },
+        "angle": {
+            "type": "integer",
+            "enum": [0, 90, 180, 270],
+            "description": "rotate: угол поворота по часовой стрелке в градусах"
+        }
+    },
+    "required": ["operation"],
+    "additionalProperties": false
+}"#;
+
+plugin_sdk::export_plugin! {
+    transform: Transform,
+    name: c"transform_plugin",
+    description: c"Обрезка, масштабирование и поворот на 90° с изменением размеров",
+    params_schema: PARAMS_SCHEMA,
+}
+
+/// Параметры: поле `operation` выбирает операцию, остальные поля зависят от неё.
+///
+/// - `{"operation": "crop", "x": u32, "y": u32, "width": u32, "height": u32}`
+/// - `{"operation": "resize", "width": u32, "height": u32}` — ближайший сосед
+/// - `{"operation": "rotate", "angle": 0 | 90 | 180 | 270}` — по часовой стрелке
+///
+/// Без параметров изображение возвращается без изменений.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "operation", rename_all = "lowercase", deny_unknown_fields)]
+enum Params {
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    Resize {
+        width: u32,
+        height: u32,
+    },
+    Rotate {
+        angle: u32,
+    },
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params::Rotate { angle: 0 }
+    }
+}
+
+/// Операции, меняющие размеры изображения.
+struct Transform;
+
+impl TransformPlugin for Transform {
+    type Params = Params;
+
+    fn transform(image: ImageRef<'_>, params: Params, context: &Context<'_>) -> Result<Image> {
+        match params {
+            Params::Crop {
+                x,
+                y,
+                width,
+                height,
+            } => crop(image, x, y, width, height, context),
+            Params::Resize { width, height } => resize(image, width, height, context),
+            Params::Rotate { angle } => rotate(image, angle, context),
+        }
+    }
+}
+
+/// Вырезает область `width × height` с левым верхним углом в `(x, y)`.
+fn crop(
+    image: ImageRef<'_>,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    context: &Context<'_>,
+) -> Result<Image> {
+    let fits =
+        |start: u32, len: u32, limit: u32| start.checked_add(len).is_some_and(|end| end <= limit);
+    if !fits(x, width, image.width()) || !fits(y, height, image.height()) {
+        return Err(PluginError::invalid_params(format!(
+            "область {width}×{height} в ({x}, {y}) выходит за изображение {}×{}",
+            image.width(),
+            image.height()
+        )));
+    }
+
+    let (src_width, _) = image.dimensions();
+    let row_len = width as usize * 4;
+    let mut data = Vec::with_capacity(row_len * height as usize);
+    for row in y as usize..(y + height) as usize {
+        context.checkpoint((row - y as usize) as f32 / height as f32, "обрезка")?;
+        let start = (row * src_width + x as usize) * 4;
+        data.extend_from_slice(&image.as_bytes()[start..start + row_len]);
+    }
+    Image::from_vec(width, height, data)
+}
+
+/// Масштабирует до `width × height` методом ближайшего соседа.
+fn resize(image: ImageRef<'_>, width: u32, height: u32, context: &Context<'_>) -> Result<Image> {
+    if width == 0 || height == 0 {
+        return Err(PluginError::invalid_params(
+            "размеры результата должны быть положительными",
+        ));
+    }
+    let mut output = Image::new(width, height)?;
+    if image.width() == 0 || image.height() == 0 {
+        return Ok(output);
+    }
+
+    // Центр пикселя результата отображается в ближайший пиксель исходника
+    let nearest = |dst: u32, dst_len: u32, src_len: u32| {
+        ((u64::from(dst) * 2 + 1) * u64::from(src_len) / (u64::from(dst_len) * 2)) as u32
+    };
+    let mut view = output.view_mut();
+    for y in 0..height {
+        context.checkpoint(y as f32 / height as f32, "масштабирование")?;
+        let src_y = nearest(y, height, image.height());
+        for x in 0..width {
+            let src_x = nearest(x, width, image.width());
+            view.set_pixel(x, y, image.pixel(src_x, src_y));
+        }
+    }
+    Ok(output)
+}
+
+/// Поворачивает на `angle` градусов по часовой стрелке.
+fn rotate(image: ImageRef<'_>, angle: u32, context: &Context<'_>) -> Result<Image> {
+    let (width, height) = (image.width(), image.height());
+    let (out_width, out_height) = match angle {
+        0 | 180 => (width, height),
+        90 | 270 => (height, width),
+        _ => {
+            return Err(PluginError::invalid_params(format!(
+                "угол {angle}° не кратен 90"
+            )));
+        }
+    };
+
+    let mut output = Image::new(out_width, out_height)?;
+    let mut view = output.view_mut();
+    for y in 0..height {
+        context.checkpoint(y as f32 / height as f32, "поворот")?;
+        for x in 0..width {
+            let (dst_x, dst_y) = match angle {
+                0 => (x, y),
+                90 => (height - 1 - y, x),
+                180 => (width - 1 - x, height - 1 - y),
+                _ => (y, width - 1 - x),
+            };
+            view.set_pixel(dst_x, dst_y, image.pixel(x, y));
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_sdk::ERROR_INVALID_PARAMS;
+    use plugin_sdk::abi::{CAPABILITY_TRANSFORM, OutputImage};
+    use std::ffi::CString;
+
+    /// Изображение `width × height`, в котором пиксель `(x, y)` равен `[x, y, 0, 255]`
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        (0..height)
+            .flat_map(|y| (0..width).flat_map(move |x| [x as u8, y as u8, 0, 255]))
+            .collect()
+    }
+
+    /// Вызывает `transform_image` через FFI и возвращает код, размеры и пиксели
+    fn call_transform_image(
+        width: u32,
+        height: u32,
+        data: &[u8],
+        params_json: &str,
+    ) -> (i32, u32, u32, Vec<u8>) {
+        let params = CString::new(params_json).unwrap();
+        let mut output = OutputImage::default();
+        let status = unsafe {
+            transform_image(
+                width,
+                height,
+                data.as_ptr(),
+                params.as_ptr(),
+                std::ptr::null(),
+                &mut output,
+            )
+        };
+        if status != 0 {
+            return (status, 0, 0, Vec::new());
+        }
+        let pixels = unsafe { std::slice::from_raw_parts(output.data, output.len) }.to_vec();
+        unsafe { free_image(output.data, output.len) };
+        (status, output.width, output.height, pixels)
+    }
+
+    #[test]
+    fn test_crop() {
+        let data = gradient(4, 3);
+        let (status, width, height, pixels) = call_transform_image(
+            4,
+            3,
+            &data,
+            r#"{"operation": "crop", "x": 1, "y": 1, "width": 2, "height": 2}"#,
+        );
+
+        assert_eq!(status, 0);
+        assert_eq!((width, height), (2, 2));
+        assert_eq!(
+            pixels,
+            vec![1, 1, 0, 255, 2, 1, 0, 255, 1, 2, 0, 255, 2, 2, 0, 255]
+        );
+    }
+
+    #[test]
+    fn test_crop_out_of_bounds() {
+        let data = gradient(4, 3);
+        let (status, ..) = call_transform_image(
+            4,
+            3,
+            &data,
+            r#"{"operation": "crop", "x": 3, "y": 0, "width": 2, "height": 1}"#,
+        );
+        assert_eq!(status, ERROR_INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_resize_nearest() {
+        let data = gradient(2, 1);
+        let (status, width, height, pixels) = call_transform_image(
+            2,
+            1,
+            &data,
+            r#"{"operation": "resize", "width": 4, "height": 2}"#,
+        );
+
+        assert_eq!(status, 0);
+        assert_eq!((width, height), (4, 2));
+        let columns: Vec<u8> = pixels.chunks(4).map(|pixel| pixel[0]).collect();
+        assert_eq!(columns, vec![0, 0, 1, 1, 0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn test_rotate_non_square() {
+        // 3×2 → 2×3; верхняя строка исходника становится правым столбцом
+        let data = gradient(3, 2);
+        let (status, width, height, pixels) =
+            call_transform_image(3, 2, &data, r#"{"operation": "rotate", "angle": 90}"#);
+
+        assert_eq!(status, 0);
+        assert_eq!((width, height), (2, 3));
+        let coords: Vec<(u8, u8)> = pixels.chunks(4).map(|p| (p[0], p[1])).collect();
+        assert_eq!(coords, vec![(0, 1), (0, 0), (1, 1), (1, 0), (2, 1), (2, 0)]);
+    }
+
+    #[test]
+    fn test_rotate_full_turn() {
+        let data = gradient(3, 2);
+        let mut image = Image::from_vec(3, 2, data.clone()).unwrap();
+        for angle in [90, 180, 270, 180] {
+            image = rotate(image.as_image_ref(), angle, &Context::none()).unwrap();
+        }
+        assert_eq!(image.into_raw(), (3, 2, data));
+    }
+
+    #[test]
+    fn test_params_without_operation_fields() {
+        // Поле чужой операции — ошибка, а не молчаливое игнорирование
+        let data = gradient(2, 2);
+        let (status, ..) =
+            call_transform_image(2, 2, &data, r#"{"operation": "rotate", "width": 1}"#);
+        assert_eq!(status, ERROR_INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_descriptor() {
+        let descriptor = unsafe { &*plugin_descriptor() };
+
+        assert_eq!(descriptor.capabilities, CAPABILITY_TRANSFORM);
+        assert_eq!(
+            unsafe { CStr::from_ptr(descriptor.name) }.to_str(),
+            Ok("transform_plugin")
+        );
+        let schema: serde_json::Value =
+            serde_json::from_str(PARAMS_SCHEMA.to_str().unwrap()).unwrap();
+        assert_eq!(schema["type"], "object");
+    }
+}